@@ -42,7 +42,7 @@ fn spawn_many_busy(mut pool: BusyLocalPool, iter: usize) {
         let cnt = cnt.clone();
         pool.spawn(Box::pin(lazy(move |_| {
             cnt.set(cnt.get() + 1);
-        })));
+        }))).unwrap();
     }
 
     pool.run();
@@ -50,17 +50,76 @@ fn spawn_many_busy(mut pool: BusyLocalPool, iter: usize) {
     assert_eq!(cnt.get(), iter);
 }
 
+fn poll_once_busy(mut pool: BusyLocalPool, iter: usize) {
+    let cnt = Rc::new(Cell::new(0));
+
+    for _ in 0..iter {
+        let cnt = cnt.clone();
+        pool.spawn(Box::pin(lazy(move |_| {
+            cnt.set(cnt.get() + 1);
+        }))).unwrap();
+    }
+
+    while pool.poll_once().is_ready() && cnt.get() < iter {}
+
+    assert_eq!(cnt.get(), iter);
+}
+
+/// A pool holding `idle` tasks that are waiting and will never be woken.
+fn with_idle_busy(idle: usize) -> BusyLocalPool<'static> {
+    let mut pool = BusyLocalPool::new(idle + 1);
+    for _ in 0..idle {
+        pool.spawn(Box::pin(futures::future::pending())).unwrap();
+    }
+    assert!(pool.poll_once().is_pending());
+    pool
+}
+
+fn with_idle_new(idle: usize) -> NewLocalPool<'static> {
+    let mut pool = NewLocalPool::new();
+    for _ in 0..idle {
+        pool.spawn(Box::pin(futures::future::pending()));
+    }
+    assert!(pool.poll_once().is_pending());
+    pool
+}
+
+/// Runs one ready task next to the idle ones, which the busy pool polls on every
+/// sweep while `FuturesUnordered` skips them.
+fn one_among_idle_busy(mut pool: BusyLocalPool<'static>) -> BusyLocalPool<'static> {
+    pool.spawn(Box::pin(lazy(|_| ()))).unwrap();
+    while pool.poll_once().is_pending() {}
+    pool
+}
+
+fn one_among_idle_new(mut pool: NewLocalPool<'static>) -> NewLocalPool<'static> {
+    pool.spawn(Box::pin(lazy(|_| ())));
+    while pool.poll_once().is_pending() {}
+    pool
+}
+
 pub fn criterion_benchmark(c: &mut Criterion) {
     for i in [2, 20, 200] {
         c.bench_function(&format!("spawn_many_old {}", i), |b|
-            b.iter_with_setup(|| LocalPool::new(), |p| spawn_many_old(p, i)),
+            b.iter_with_setup(LocalPool::new, |p| spawn_many_old(p, i)),
         );
         c.bench_function(&format!("spawn_many_new {}", i), |b|
-            b.iter_with_setup(|| NewLocalPool::new(), |p| spawn_many_new(p, i)),
+            b.iter_with_setup(NewLocalPool::new, |p| spawn_many_new(p, i)),
         );
         c.bench_function(&format!("spawn_many_busy {}", i), |b|
             b.iter_with_setup(|| BusyLocalPool::new(256), |p| spawn_many_busy(p, i)),
         );
+        c.bench_function(&format!("poll_once_busy {}", i), |b|
+            b.iter_with_setup(|| BusyLocalPool::new(256), |p| poll_once_busy(p, i)),
+        );
+    }
+    for idle in [16, 64, 256, 1024] {
+        c.bench_function(&format!("one_among_idle_new {}", idle), |b|
+            b.iter_with_setup(|| with_idle_new(idle), one_among_idle_new),
+        );
+        c.bench_function(&format!("one_among_idle_busy {}", idle), |b|
+            b.iter_with_setup(|| with_idle_busy(idle), one_among_idle_busy),
+        );
     }
 }
 
@@ -0,0 +1,45 @@
+/// How [`block_fn_with`](crate::block_fn_with) waits between checks for a wake-up.
+///
+/// The default matches [`block_fn`](crate::block_fn): a spin-loop hint on every
+/// check and no yielding.
+///
+/// ```rust
+/// use minimal_executor::{block_fn_with, BlockConfig};
+///
+/// let config = BlockConfig { yield_every: Some(64), ..BlockConfig::default() };
+/// assert_eq!(block_fn_with(config, |_| core::task::Poll::Ready(1)), 1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockConfig {
+    /// Call [`core::hint::spin_loop`] on each check, easing the load on the core and
+    /// the cache line the waker writes to.
+    pub spin_hint: bool,
+    /// Call [`std::thread::yield_now`] after this many checks in a row found no
+    /// wake-up. Ignored without the `std` feature.
+    pub yield_every: Option<usize>,
+}
+
+impl Default for BlockConfig {
+    fn default() -> Self {
+        Self {
+            spin_hint: true,
+            yield_every: None,
+        }
+    }
+}
+
+impl BlockConfig {
+    /// Waits after the `checks`-th check in a row that found no wake-up.
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    pub(crate) fn pause(&self, checks: usize) {
+        if self.spin_hint {
+            core::hint::spin_loop();
+        }
+        #[cfg(feature = "std")]
+        if let Some(every) = self.yield_every {
+            if checks.is_multiple_of(every) {
+                std::thread::yield_now();
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use alloc::rc::Rc;
+use core::cell::Cell;
+
+/// A poll budget shared by several pools running on the same thread.
+///
+/// Every task poll made through a `poll_budgeted` method consumes one unit. Once the
+/// budget reaches zero, all pools sharing it stop polling until it is refilled, which
+/// caps the total amount of work done per frame across the whole group.
+#[derive(Debug, Clone)]
+pub struct PollBudget {
+    remaining: Rc<Cell<usize>>,
+}
+
+impl PollBudget {
+    pub fn new(polls: usize) -> Self {
+        Self {
+            remaining: Rc::new(Cell::new(polls)),
+        }
+    }
+    pub fn remaining(&self) -> usize {
+        self.remaining.get()
+    }
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining.get() == 0
+    }
+    /// Reset the budget, typically at the start of a new frame.
+    pub fn refill(&self, polls: usize) {
+        self.remaining.set(polls)
+    }
+    pub(crate) fn consume(&self) {
+        self.remaining.set(self.remaining.get().saturating_sub(1))
+    }
+}
+
+/// The error [`block_on_budget`](crate::block_on_budget) returns when its future did
+/// not complete within the allowed number of polls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetExceeded {
+    /// How many times the future was polled.
+    pub polls: usize,
+}
+
+impl core::fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "future still pending after {} polls", self.polls)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BudgetExceeded {}
+
+/// The error `run_bounded` returns when the pool went too many rounds in a row
+/// without completing a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StuckError {
+    /// How many tasks were still in the pool.
+    pub pending: usize,
+}
+
+impl core::fmt::Display for StuckError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "pool stuck with {} pending tasks", self.pending)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StuckError {}
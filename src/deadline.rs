@@ -0,0 +1,50 @@
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures::channel::oneshot;
+use futures::FutureExt;
+
+/// The error a [`DeadlineHandle`] resolves to when its task missed the deadline, and
+/// [`block_on_timeout`](crate::block_on_timeout) returns when its future ran out of time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeout;
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("task deadline exceeded")
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+/// A future resolving to the output of a task spawned with a deadline.
+///
+/// Resolves to `Err(Timeout)` if the pool dropped the task because the deadline
+/// passed, or because the pool itself was dropped first.
+#[derive(Debug)]
+pub struct DeadlineHandle<T> {
+    rx: oneshot::Receiver<Result<T, Timeout>>,
+}
+
+impl<T> Future for DeadlineHandle<T> {
+    type Output = Result<T, Timeout>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx).poll(cx).map(|ret| ret.unwrap_or(Err(Timeout)))
+    }
+}
+
+/// Wraps `fut` into a task that reports its output to the returned handle.
+///
+/// The pool enforces the deadline: dropping the task before it completes drops the
+/// sender, which resolves the handle to `Err(Timeout)`.
+pub(crate) fn with_handle<'a, Fut>(fut: Fut) -> (impl Future<Output = ()> + 'a, DeadlineHandle<Fut::Output>)
+    where Fut: Future + 'a {
+    let (tx, rx) = oneshot::channel();
+    let task = fut.map(move |ret| {
+        // the handle may already be gone, in which case nobody wants the result
+        let _ = tx.send(Ok(ret));
+    });
+    (task, DeadlineHandle { rx })
+}
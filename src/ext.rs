@@ -0,0 +1,188 @@
+use core::future::Future;
+use core::task::{Context, Poll};
+use crate::budget::StuckError;
+use crate::poll_fn;
+use crate::queue::TaskQueue;
+
+/// Methods shared by every pool, for code that should work with any of them.
+///
+/// Only [`poll_with`](PoolExt::poll_with) has to be implemented; the rest is built on
+/// it. The pools have inherent methods of the same names, which take precedence when
+/// the pool type is known.
+///
+/// ```rust
+/// use minimal_executor::prelude::*;
+///
+/// fn first_two<P: PoolExt<Output = u32>>(pool: &mut P) -> Vec<u32> {
+///     pool.run_n(2)
+/// }
+///
+/// let mut pool = BusyLocalPool::new(4);
+/// for i in 0..3 {
+///     pool.spawn(Box::pin(async move { i })).unwrap();
+/// }
+/// assert_eq!(first_two(&mut pool), vec![0, 1]);
+/// ```
+pub trait PoolExt {
+    /// What the pool's tasks resolve to.
+    type Output;
+
+    /// Polls the tasks with `cx`, see e.g. [`NewLocalPool::poll_with`](crate::NewLocalPool::poll_with).
+    fn poll_with(&mut self, cx: &mut Context<'_>) -> Poll<Option<Self::Output>>;
+
+    /// Number of unfinished tasks.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Run tasks until `max` of them have completed or none of the rest can make
+    /// progress, returning the results in completion order.
+    fn run_n(&mut self, max: usize) -> alloc::vec::Vec<Self::Output> {
+        let mut results = alloc::vec::Vec::new();
+        while results.len() < max {
+            match poll_fn(|cx| self.poll_with(cx)) {
+                Poll::Ready(Some(r)) => results.push(r),
+                Poll::Ready(None) | Poll::Pending => break,
+            }
+        }
+        results
+    }
+
+    /// Runs the tasks until `f` completes, and returns its output.
+    ///
+    /// `f` is polled once per pass over the tasks, before the tasks themselves. Tasks
+    /// still pending when `f` completes stay in the pool; the results of tasks that
+    /// completed in the meantime are dropped.
+    fn run_until<F: Future>(&mut self, f: F) -> F::Output {
+        futures::pin_mut!(f);
+        loop {
+            if let Poll::Ready(t) = poll_fn(|cx| f.as_mut().poll(cx)) {
+                return t;
+            }
+            let _ = poll_fn(|cx| self.poll_with(cx));
+        }
+    }
+
+    /// Like `run`, but gives up once `max_idle_rounds` passes over the tasks in a row
+    /// completed none of them.
+    ///
+    /// Turns the hang of a pool whose tasks can never complete into an error that says
+    /// how many are stuck. The tasks stay in the pool and the results of those that
+    /// completed are dropped. Tasks waiting on other threads or timers may need many
+    /// rounds, so pick a generous bound for them.
+    fn run_bounded(&mut self, max_idle_rounds: usize) -> Result<alloc::vec::Vec<Self::Output>, StuckError> {
+        let mut results = alloc::vec::Vec::new();
+        let mut idle = 0;
+        loop {
+            match poll_fn(|cx| self.poll_with(cx)) {
+                Poll::Ready(Some(r)) => {
+                    results.push(r);
+                    idle = 0;
+                }
+                Poll::Ready(None) => return Ok(results),
+                Poll::Pending => {
+                    idle += 1;
+                    if idle >= max_idle_rounds {
+                        return Err(StuckError { pending: self.len() });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Iterate over results as tasks complete, see [`RunIter`].
+    fn run_iter(&mut self) -> RunIter<'_, Self> {
+        RunIter::new(self)
+    }
+}
+
+/// Iterator that runs a pool lazily, yielding each result as its task completes.
+///
+/// Each `next` polls the pool until a task completes, which blocks like
+/// [`run`](crate::NewLocalPool::run) while nothing is ready, and returns `None` once the
+/// pool is empty. Stopping early leaves the remaining tasks in the pool.
+///
+/// ```rust
+/// use minimal_executor::NewLocalPool;
+///
+/// let mut pool = NewLocalPool::new();
+/// for i in 0..4 {
+///     pool.spawn(Box::pin(async move { i }));
+/// }
+/// assert_eq!(pool.run_iter().take(3).count(), 3);
+/// assert_eq!(pool.len(), 1);
+/// ```
+#[derive(Debug)]
+pub struct RunIter<'p, P: ?Sized> {
+    pool: &'p mut P,
+}
+
+impl<'p, P: ?Sized> RunIter<'p, P> {
+    pub(crate) fn new(pool: &'p mut P) -> Self {
+        Self { pool }
+    }
+}
+
+impl<P: PoolExt + ?Sized> Iterator for RunIter<'_, P> {
+    type Item = P::Output;
+
+    fn next(&mut self) -> Option<P::Output> {
+        loop {
+            match poll_fn(|cx| self.pool.poll_with(cx)) {
+                Poll::Ready(ret) => return ret,
+                Poll::Pending => {}
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, Ret> PoolExt for crate::LocalPool<'a, Ret> {
+    type Output = Ret;
+
+    fn poll_with(&mut self, cx: &mut Context<'_>) -> Poll<Option<Ret>> {
+        crate::LocalPool::poll_with(self, cx)
+    }
+
+    fn len(&self) -> usize {
+        crate::LocalPool::len(self)
+    }
+}
+
+impl<'a, Ret> PoolExt for crate::NewLocalPool<'a, Ret> {
+    type Output = Ret;
+
+    fn poll_with(&mut self, cx: &mut Context<'_>) -> Poll<Option<Ret>> {
+        crate::NewLocalPool::poll_with(self, cx)
+    }
+
+    fn len(&self) -> usize {
+        crate::NewLocalPool::len(self)
+    }
+}
+
+impl<'a, Ret> PoolExt for crate::LifoLocalPool<'a, Ret> {
+    type Output = Ret;
+
+    fn poll_with(&mut self, cx: &mut Context<'_>) -> Poll<Option<Ret>> {
+        crate::LifoLocalPool::poll_with(self, cx)
+    }
+
+    fn len(&self) -> usize {
+        crate::LifoLocalPool::len(self)
+    }
+}
+
+impl<'a, Ret: 'a, Q: TaskQueue<crate::BusyTask<'a, Ret>>> PoolExt for crate::BusyLocalPool<'a, Ret, Q> {
+    type Output = Ret;
+
+    fn poll_with(&mut self, cx: &mut Context<'_>) -> Poll<Option<Ret>> {
+        crate::BusyLocalPool::poll_with(self, cx)
+    }
+
+    fn len(&self) -> usize {
+        crate::BusyLocalPool::len(self)
+    }
+}
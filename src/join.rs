@@ -0,0 +1,55 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures::channel::oneshot::{self, Canceled};
+use futures::future::{abortable, AbortHandle};
+use futures::FutureExt;
+
+/// A future resolving to the output of a task spawned with a handle.
+///
+/// Resolves to `Err(Canceled)` if the task was dropped before completing, e.g.
+/// because the pool it was spawned on was dropped first.
+#[derive(Debug)]
+pub struct JoinHandle<T> {
+    rx: oneshot::Receiver<T>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Blocks the current thread until the task has completed or been dropped.
+    #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+    pub fn block(self) -> Result<T, Canceled> {
+        crate::block_on(self)
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, Canceled>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx).poll(cx)
+    }
+}
+
+/// Wraps `fut` into a task that sends its output to the returned handle.
+pub(crate) fn with_handle<Fut: Future>(fut: Fut) -> (impl Future<Output = ()>, JoinHandle<Fut::Output>) {
+    let (tx, rx) = oneshot::channel();
+    let task = fut.map(move |ret| {
+        // the handle may already be gone, in which case nobody wants the result
+        let _ = tx.send(ret);
+    });
+    (task, JoinHandle { rx })
+}
+
+/// Like [`with_handle`], but the task can also be cancelled through the returned
+/// [`AbortHandle`]. An aborted task completes without polling `fut` again, dropping
+/// it, and its handle resolves to `Err(Canceled)`.
+pub(crate) fn with_abort_handle<Fut: Future>(fut: Fut) -> (impl Future<Output = ()>, AbortHandle, JoinHandle<Fut::Output>) {
+    let (tx, rx) = oneshot::channel();
+    let (fut, abort) = abortable(fut);
+    let task = fut.map(move |ret| {
+        if let Ok(ret) = ret {
+            let _ = tx.send(ret);
+        }
+    });
+    (task, abort, JoinHandle { rx })
+}
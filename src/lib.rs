@@ -1,30 +1,153 @@
 #![no_std]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
 mod local_pool_new;
+#[cfg(feature = "std")]
 mod local_pool_old;
-pub(crate) mod waker;
+pub mod waker;
 mod local_pool_busy;
+mod local_pool_lifo;
+mod queue;
+mod budget;
+mod scope;
+mod recommend;
+mod middleware;
+mod limit;
+mod slab;
+mod join;
+mod local_spawn;
+#[cfg(feature = "trace-events")]
+mod trace;
+#[cfg(feature = "std")]
+mod deadline;
+#[cfg(feature = "std")]
+mod tracked;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod ext;
+mod spawn_error;
+mod block_config;
+mod observer;
+mod yield_now;
+pub mod prelude;
 
+#[cfg(feature = "std")]
 pub use crate::local_pool_old::*;
 pub use crate::local_pool_new::LocalPool as NewLocalPool;
 pub use crate::local_pool_new::Spawner as NewSpawner;
 pub use crate::local_pool_busy::Spawner as BusySpawner;
 pub use crate::local_pool_busy::LocalPool as BusyLocalPool;
+pub use crate::local_pool_busy::Task as BusyTask;
+pub use crate::local_pool_lifo::LocalPool as LifoLocalPool;
+pub use crate::local_pool_lifo::Spawner as LifoSpawner;
+pub use crate::local_pool_busy::{AppendError, PausePoint, PoolStats, RunOutcome, RunTick, StepOutcome, TaskGuard};
+pub use crate::waker::{WakerStrategy, LocalWake, local_waker, SimpleWaker, waker_from_arc};
+pub use crate::budget::{BudgetExceeded, PollBudget, StuckError};
+pub use crate::queue::{BusyQueue, TaskQueue};
+pub use crate::scope::{scope, Scope};
+pub use crate::recommend::{recommended_pool, PoolKind};
+pub use crate::slab::TaskId;
+pub use crate::join::JoinHandle;
+pub use crate::local_spawn::LocalSpawner;
+pub use crate::ext::{PoolExt, RunIter};
+pub use crate::spawn_error::{SpawnErrorKind, TrySpawnError};
+pub use crate::block_config::BlockConfig;
+pub use crate::observer::PollState;
+pub use crate::yield_now::{yield_now, YieldNow};
+#[cfg(feature = "trace-events")]
+pub use crate::trace::Event;
+#[cfg(feature = "metrics")]
+pub use crate::local_pool_busy::PoolMetrics;
+#[cfg(feature = "std")]
+pub use crate::deadline::{DeadlineHandle, Timeout};
+#[cfg(feature = "std")]
+pub use crate::tracked::CompletionHandle;
+#[cfg(feature = "wasm")]
+pub use crate::wasm::spawn_on_microtask;
 
 use core::future::{Future};
 use core::task::{Poll, Context};
-use crate::waker::{AlwaysWake, waker_ref};
+use alloc::sync::Arc;
+use futures::future::Either;
+use crate::waker::{always_waker, SingleWake, TaskWake};
+#[cfg(feature = "std")]
+use crate::waker::ParkWaker;
+
+/// Runs `run` on `pool`; if it panics, calls `clear` before resuming the unwind so the
+/// pool's remaining tasks are dropped rather than left behind.
+#[cfg(feature = "std")]
+pub(crate) fn drop_on_panic<P, T>(pool: &mut P, run: impl FnOnce(&mut P) -> T, clear: impl FnOnce(&mut P)) -> T {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| run(&mut *pool))) {
+        Ok(t) => t,
+        Err(panic) => {
+            clear(pool);
+            std::panic::resume_unwind(panic)
+        }
+    }
+}
+
+/// Without `std` there is no unwinding to guard against; just runs `run`.
+#[cfg(not(feature = "std"))]
+pub(crate) fn drop_on_panic<P, T>(pool: &mut P, run: impl FnOnce(&mut P) -> T, _clear: impl FnOnce(&mut P)) -> T {
+    run(pool)
+}
+
+/// Writes the one-line summary the pools print with `Display`, e.g.
+/// `pool: 3/256 tasks, 1024 completed, 2 overdue`. The capacity is left out for pools
+/// without a cap, and the overdue count for pools without deadlines.
+pub(crate) fn write_summary(f: &mut core::fmt::Formatter<'_>, len: usize, cap: Option<usize>,
+                            completed: usize, overdue: Option<usize>) -> core::fmt::Result {
+    write!(f, "pool: {}", len)?;
+    if let Some(cap) = cap {
+        write!(f, "/{}", cap)?;
+    }
+    write!(f, " tasks, {} completed", completed)?;
+    if let Some(overdue) = overdue {
+        write!(f, ", {} overdue", overdue)?;
+    }
+    Ok(())
+}
 
 pub fn poll_fn<T, F: FnOnce(&mut Context<'_>) -> T>(f: F) -> T {
-    let waker = waker_ref(&AlwaysWake::INSTANCE);
-    let mut cx = Context::from_waker(&waker);
+    let mut cx = Context::from_waker(always_waker());
     f(&mut cx)
 }
 
-pub fn block_fn<T, F: FnMut(&mut Context<'_>) -> Poll<T>>(mut f: F) -> T {
-    let waker = waker_ref(&AlwaysWake::INSTANCE);
+/// Polls `f` until it is ready, spinning between polls until it has been woken.
+///
+/// Only re-polls after a wake-up, so a closure that returns `Pending` without
+/// arranging one blocks forever; use [`block_fn_hot`] for those.
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub fn block_fn<T, F: FnMut(&mut Context<'_>) -> Poll<T>>(f: F) -> T {
+    block_fn_with(BlockConfig::default(), f)
+}
+
+/// Like [`block_fn`], but waits for wake-ups as `config` says, e.g. yielding the
+/// thread now and then while other threads compete for the core.
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub fn block_fn_with<T, F: FnMut(&mut Context<'_>) -> Poll<T>>(config: BlockConfig, mut f: F) -> T {
+    let wake = Arc::new(SingleWake::new());
+    let waker = waker_from_arc(wake.clone());
     let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(t) = f(&mut cx) {
+            return t;
+        }
+        let mut checks = 0;
+        while !wake.read_reset() {
+            checks += 1;
+            config.pause(checks);
+        }
+    }
+}
+
+/// Like [`block_fn`], but re-polls `f` straight away whether or not it was woken.
+///
+/// Reacts to readiness a little sooner, at the cost of polling continuously.
+pub fn block_fn_hot<T, F: FnMut(&mut Context<'_>) -> Poll<T>>(mut f: F) -> T {
+    let mut cx = Context::from_waker(always_waker());
     loop {
         if let Poll::Ready(t) = f(&mut cx) {
             return t;
@@ -37,8 +160,359 @@ pub fn poll_on<T, Fut: Future<Output = T>>(f: Fut) -> Poll<Fut::Output> {
     poll_fn(|cx| f.as_mut().poll(cx))
 }
 
+/// Polls each of `futs` once and returns their states, in the same order.
+///
+/// Like [`poll_on`] for several futures at once, for checking which are ready without
+/// a pool. A future that already returned `Ready` must not be passed in again.
+///
+/// ```rust
+/// use core::pin::pin;
+/// use core::task::Poll;
+/// use futures::future::{pending, ready, Either};
+/// use minimal_executor::poll_all;
+///
+/// let mut a = pin!(Either::Left(ready(1)));
+/// let mut b = pin!(Either::Right(pending()));
+/// assert_eq!(poll_all(&mut [a.as_mut(), b.as_mut()]), vec![Poll::Ready(1), Poll::Pending]);
+/// ```
+pub fn poll_all<T, Fut: Future<Output = T> + ?Sized>(futs: &mut [core::pin::Pin<&mut Fut>]) -> alloc::vec::Vec<Poll<T>> {
+    poll_fn(|cx| futs.iter_mut().map(|f| f.as_mut().poll(cx)).collect())
+}
 
+
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
 pub fn block_on<T, Fut: Future<Output = T>>(f: Fut) -> Fut::Output {
     futures::pin_mut!(f);
     block_fn(|cx| f.as_mut().poll(cx))
-}
\ No newline at end of file
+}
+
+/// Blocks on `a` and `b` together and returns the output of whichever completes first.
+///
+/// Both futures are polled on every wake-up, `a` first, so `a` wins a tie. The other
+/// future is dropped before this returns.
+///
+/// ```rust
+/// use futures::future::{pending, Either};
+/// use minimal_executor::select_block_on;
+///
+/// assert!(matches!(select_block_on(pending::<()>(), async { 2 }), Either::Right(2)));
+/// assert!(matches!(select_block_on(async { 1 }, async { 2 }), Either::Left(1)));
+/// ```
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub fn select_block_on<A: Future, B: Future>(a: A, b: B) -> Either<A::Output, B::Output> {
+    futures::pin_mut!(a, b);
+    block_fn(|cx| {
+        if let Poll::Ready(t) = a.as_mut().poll(cx) {
+            return Poll::Ready(Either::Left(t));
+        }
+        b.as_mut().poll(cx).map(Either::Right)
+    })
+}
+
+/// Blocks on `a` and `b` together until both have completed, returning both outputs.
+///
+/// Each wake-up polls whichever of the two is still pending; the output of the one
+/// that finishes first is kept until the other is done.
+///
+/// ```rust
+/// use minimal_executor::join_block_on;
+///
+/// assert_eq!(join_block_on(async { 1 }, async { "two" }), (1, "two"));
+/// ```
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub fn join_block_on<A: Future, B: Future>(a: A, b: B) -> (A::Output, B::Output) {
+    futures::pin_mut!(a, b);
+    let (mut left, mut right) = (None, None);
+    block_fn(|cx| {
+        if left.is_none() {
+            if let Poll::Ready(t) = a.as_mut().poll(cx) {
+                left = Some(t);
+            }
+        }
+        if right.is_none() {
+            if let Poll::Ready(t) = b.as_mut().poll(cx) {
+                right = Some(t);
+            }
+        }
+        if left.is_none() || right.is_none() {
+            return Poll::Pending;
+        }
+        Poll::Ready((left.take().unwrap(), right.take().unwrap()))
+    })
+}
+
+/// Like [`block_on`], but also returns how many polls the future took to complete.
+///
+/// A count far above the number of real events the future waits on points at
+/// needless self-wakes or busy-looping.
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub fn block_on_profiled<Fut: Future>(f: Fut) -> (Fut::Output, usize) {
+    futures::pin_mut!(f);
+    let mut polls = 0;
+    let ret = block_fn(|cx| {
+        polls += 1;
+        f.as_mut().poll(cx)
+    });
+    (ret, polls)
+}
+
+/// Like [`block_on_profiled`], but also says whether the future was ready on its first
+/// poll, the fast path most futures are expected to take.
+///
+/// ```rust
+/// use minimal_executor::block_on_instrumented;
+///
+/// assert_eq!(block_on_instrumented(async { 1 }), (1, 1, true));
+/// let mut yielded = false;
+/// let yield_once = futures::future::poll_fn(|cx| {
+///     if std::mem::replace(&mut yielded, true) {
+///         return std::task::Poll::Ready(2);
+///     }
+///     cx.waker().wake_by_ref();
+///     std::task::Poll::Pending
+/// });
+/// assert_eq!(block_on_instrumented(yield_once), (2, 2, false));
+/// ```
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub fn block_on_instrumented<T, Fut: Future<Output = T>>(f: Fut) -> (T, usize, bool) {
+    let (ret, polls) = block_on_profiled(f);
+    (ret, polls, polls == 1)
+}
+
+/// Like [`block_on`], but gives up once the future has been polled `max_polls` times
+/// without completing, dropping it.
+///
+/// Re-polls straight away like [`block_fn_hot`], so a future that never arranges a
+/// wake-up uses up its budget instead of hanging. A completion on the last allowed
+/// poll counts as success.
+///
+/// ```rust
+/// use minimal_executor::{block_on_budget, BudgetExceeded};
+///
+/// assert_eq!(block_on_budget(async { 1 }, 1), Ok(1));
+/// let stuck = futures::future::pending::<()>();
+/// assert_eq!(block_on_budget(stuck, 100), Err(BudgetExceeded { polls: 100 }));
+/// ```
+pub fn block_on_budget<T, Fut: Future<Output = T>>(f: Fut, max_polls: usize) -> Result<T, BudgetExceeded> {
+    futures::pin_mut!(f);
+    let mut polls = 0;
+    block_fn_hot(|cx| {
+        if polls == max_polls {
+            return Poll::Ready(Err(BudgetExceeded { polls }));
+        }
+        polls += 1;
+        f.as_mut().poll(cx).map(Ok)
+    })
+}
+
+/// Like [`block_on`], but runs the tasks of `pool` while waiting for `fut`.
+///
+/// Each iteration polls `fut` and then makes one pass over the pool, as
+/// [`PoolExt::run_until`] does. Tasks still pending when `fut` completes stay in the
+/// pool, so they carry on with the next run.
+///
+/// ```rust
+/// use minimal_executor::{block_on_in_pool, NewLocalPool};
+///
+/// let (tx, rx) = futures::channel::oneshot::channel();
+/// let mut pool = NewLocalPool::new();
+/// pool.spawn(Box::pin(async move { tx.send(4).unwrap() }));
+/// pool.spawn(Box::pin(futures::future::pending()));
+/// assert_eq!(block_on_in_pool(&mut pool, rx), Ok(4));
+/// assert_eq!(pool.len(), 1);
+/// ```
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub fn block_on_in_pool<P: PoolExt, Fut: Future>(pool: &mut P, fut: Fut) -> Fut::Output {
+    pool.run_until(fut)
+}
+
+/// Like [`block_on`], but also feeds every ready item of `events` to `handler` while
+/// waiting for `main`.
+///
+/// Each iteration polls `main` first and then drains the items `events` has ready.
+/// Items still pending in `events` when `main` completes are left in the stream.
+/// Since `handler` may unblock `main` without waking it, this re-polls continuously
+/// like [`block_fn_hot`].
+pub fn block_on_with_events<Fut, S, H>(main: Fut, events: S, mut handler: H) -> Fut::Output
+    where Fut: Future, S: futures::Stream, H: FnMut(S::Item) {
+    use futures::{Stream, StreamExt};
+    futures::pin_mut!(main);
+    let events = events.fuse();
+    futures::pin_mut!(events);
+    block_fn_hot(|cx| {
+        if let Poll::Ready(t) = main.as_mut().poll(cx) {
+            return Poll::Ready(t);
+        }
+        while let Poll::Ready(Some(item)) = events.as_mut().poll_next(cx) {
+            handler(item);
+        }
+        Poll::Pending
+    })
+}
+
+/// Like [`block_on`], but gives the OS a chance to run other threads whenever the
+/// future yields.
+///
+/// `block_on` has nothing else to do while its single future is pending, so a future
+/// that yields in a loop (returns `Pending` after waking itself) is re-polled straight
+/// away and keeps a core busy. This variant calls [`std::thread::yield_now`] after each
+/// such yield instead.
+#[cfg(feature = "std")]
+pub fn block_on_cooperative<T, Fut: Future<Output = T>>(f: Fut) -> Fut::Output {
+    futures::pin_mut!(f);
+    let wake = Arc::new(SingleWake::new());
+    let waker = waker_from_arc(wake.clone());
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(t) = f.as_mut().poll(&mut cx) {
+            return t;
+        }
+        if wake.read_reset() {
+            std::thread::yield_now();
+        }
+    }
+}
+
+/// Like [`block_on`], but parks the thread while the future is pending instead of
+/// spinning.
+///
+/// The future is polled once up front and then once more for every wake-up it
+/// receives; spurious unparks are ignored. Futures that are pending without having
+/// arranged a wake-up block forever.
+///
+/// ```rust
+/// use minimal_executor::block_on_parked;
+///
+/// let (tx, rx) = futures::channel::oneshot::channel();
+/// std::thread::spawn(move || tx.send(5).unwrap());
+/// assert_eq!(block_on_parked(rx), Ok(5));
+/// ```
+#[cfg(feature = "std")]
+pub fn block_on_parked<T, Fut: Future<Output = T>>(f: Fut) -> Fut::Output {
+    futures::pin_mut!(f);
+    let wake = ParkWaker::new();
+    let waker = futures::task::waker_ref(&wake);
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if wake.read_reset() {
+            if let Poll::Ready(t) = f.as_mut().poll(&mut cx) {
+                return t;
+            }
+        } else {
+            std::thread::park();
+        }
+    }
+}
+
+/// Like [`block_on`], but gives up once `timeout` has passed, dropping the future.
+///
+/// The clock is only checked between polls, so a future that completes on a poll
+/// started before the deadline counts as done, however long that poll took.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use minimal_executor::{block_on_timeout, Timeout};
+///
+/// assert_eq!(block_on_timeout(async { 1 }, Duration::from_secs(1)), Ok(1));
+/// let hung = futures::future::pending::<()>();
+/// assert_eq!(block_on_timeout(hung, Duration::from_millis(10)), Err(Timeout));
+/// ```
+#[cfg(feature = "std")]
+pub fn block_on_timeout<T, Fut: Future<Output = T>>(f: Fut, timeout: core::time::Duration) -> Result<T, Timeout> {
+    let deadline = std::time::Instant::now() + timeout;
+    futures::pin_mut!(f);
+    let wake = Arc::new(SingleWake::new());
+    let waker = waker_from_arc(wake.clone());
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(t) = f.as_mut().poll(&mut cx) {
+            return Ok(t);
+        }
+        while !wake.read_reset() {
+            if std::time::Instant::now() >= deadline {
+                return Err(Timeout);
+            }
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// Like [`block_on`], but calls `idle` instead of re-polling while the future has not
+/// been woken.
+///
+/// On bare-metal targets pass a closure that sleeps the core until the next interrupt,
+/// e.g. `|| cortex_m::asm::wfi()`. The interrupt handler that wakes the future also
+/// wakes the core, after which the future is polled again.
+///
+/// ```rust
+/// use minimal_executor::block_on_idle;
+///
+/// let mut idles = 0;
+/// assert_eq!(block_on_idle(async { 7 }, || idles += 1), 7);
+/// assert_eq!(idles, 0);
+/// ```
+pub fn block_on_idle<T, Fut, W>(f: Fut, idle: W) -> Fut::Output
+    where Fut: Future<Output = T>, W: FnMut() {
+    futures::pin_mut!(f);
+    poll_fn_woken_with(|cx| f.as_mut().poll(cx), idle)
+}
+
+/// Polls `f` until it is ready, re-polling only while it has woken its [`Context`].
+///
+/// A closure that woke itself is polled again immediately; otherwise this spins until
+/// a wake-up arrives, e.g. from another thread. This is the building block for the
+/// event-driven `block_on` variants; see [`poll_fn_woken_with`] to sleep instead.
+///
+/// ```rust
+/// use core::task::Poll;
+/// use minimal_executor::poll_fn_woken;
+///
+/// let mut polls = 0;
+/// let ret = poll_fn_woken(|cx| {
+///     polls += 1;
+///     if polls == 3 {
+///         return Poll::Ready(polls);
+///     }
+///     cx.waker().wake_by_ref();
+///     Poll::Pending
+/// });
+/// assert_eq!(ret, 3);
+/// ```
+pub fn poll_fn_woken<T, F: FnMut(&mut Context<'_>) -> Poll<T>>(f: F) -> T {
+    poll_fn_woken_with(f, core::hint::spin_loop)
+}
+
+/// Like [`poll_fn_woken`], but calls `idle` while `f` has not been woken.
+pub fn poll_fn_woken_with<T, F, W>(mut f: F, mut idle: W) -> T
+    where F: FnMut(&mut Context<'_>) -> Poll<T>, W: FnMut() {
+    let wake = TaskWake::new(WakerStrategy::SingleFlag);
+    let waker = futures::task::waker_ref(&wake);
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if wake.read_reset() {
+            if let Poll::Ready(t) = f(&mut cx) {
+                return t;
+            }
+        } else {
+            idle();
+        }
+    }
+}
+
+/// Drives an `embedded-hal-async` style future to completion by spinning.
+///
+/// On bare-metal targets there is usually no reactor to wake a task, so a HAL future
+/// (e.g. an async I2C transaction) is simply polled until the peripheral reports it is done.
+/// Unlike [`block_on`] it re-polls without waiting for a wake-up, as [`block_fn_hot`]
+/// does, and exists so embedded code reads like the `nb::block!` idiom it replaces.
+///
+/// ```rust
+/// use minimal_executor::block_hal;
+///
+/// let value = block_hal(async { 0x42u8 });
+/// assert_eq!(value, 0x42);
+/// ```
+pub fn block_hal<Fut: Future>(f: Fut) -> Fut::Output {
+    futures::pin_mut!(f);
+    block_fn_hot(|cx| f.as_mut().poll(cx))
+}
@@ -0,0 +1,39 @@
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Caps the number of tasks in an unbounded pool, shared with its spawners.
+///
+/// The count is bookkeeping only and publishes no data, so `Relaxed` is enough.
+#[derive(Debug)]
+pub(crate) struct TaskLimit {
+    max: usize,
+    live: AtomicUsize,
+}
+
+impl TaskLimit {
+    pub fn new(max: usize) -> Arc<Self> {
+        Arc::new(Self { max, live: AtomicUsize::new(0) })
+    }
+
+    /// Claims a slot for a task sent by a spawner, failing once the pool is full.
+    pub fn try_acquire(&self) -> bool {
+        if self.live.fetch_add(1, Ordering::Relaxed) >= self.max {
+            self.live.fetch_sub(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// Claims a slot for a task spawned on the pool itself, which is never refused.
+    pub fn acquire(&self) {
+        self.live.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    pub fn release(&self, tasks: usize) {
+        self.live.fetch_sub(tasks, Ordering::Relaxed);
+    }
+}
@@ -1,13 +1,30 @@
-use alloc::sync::{Arc, Weak};
+use futures::future::FusedFuture;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
 use futures::future::LocalFutureObj;
 use futures::{FutureExt};
-use core::task::{Poll};
-use crossbeam::queue::ArrayQueue;
+use core::task::{Context, Poll};
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use futures::task::UnsafeFutureObj;
-use crate::poll_fn;
+use futures::future::AbortHandle;
+use crate::join::{with_abort_handle, JoinHandle};
+use crate::{drop_on_panic, poll_fn};
 use futures::future::FutureObj;
 use futures::task::Spawn;
 use futures::task::SpawnError;
+use crate::waker::{waker_from_arc, SimpleWaker, SingleWake, TaskWake, WakerStrategy};
+use crate::budget::PollBudget;
+use crate::middleware::Middleware;
+use crate::queue::{BusyQueue, TaskQueue};
+use crate::slab::{Slab, TaskId};
+use crate::observer::{PollObserver, PollState};
+use crate::spawn_error::{SpawnErrorKind, TrySpawnError};
+#[cfg(feature = "trace-events")]
+use crate::trace::{Event, EventLog};
+use core::cell::{Cell, RefCell};
 
 /// A single-threaded task pool for polling futures to completion.
 ///
@@ -21,55 +38,1005 @@ use futures::task::SpawnError;
 /// single-threaded, it supports a special form of task spawning for non-`Send`
 /// futures, via [`spawn_local_obj`](futures_task::LocalSpawn::spawn_local_obj).
 #[derive(Debug)]
-pub struct LocalPool<'a, Ret = ()> {
-    pool: Arc<ArrayQueue<LocalFutureObj<'a, Ret>>>,
+pub struct LocalPool<'a, Ret = (), Q = BusyQueue<Task<'a, Ret>>>
+    where Q: TaskQueue<Task<'a, Ret>> {
+    shared: Arc<Shared<'a, Ret, Q>>,
+    strategy: WakerStrategy,
+    /// Run a sweep with normal tasks first after this many sweeps that never got to them.
+    fairness: Option<usize>,
+    /// Sweeps in a row that returned before reaching the normal tasks.
+    starved: Cell<usize>,
+    /// Tasks left to visit by `poll_once` in the current round, from `first` and `pool`.
+    round: Cell<(usize, usize)>,
+    /// Tasks `poll_once` already visited in the current round.
+    visited: Cell<usize>,
+    middleware: RefCell<Middleware<'a, Ret>>,
+    /// Slots of the tasks spawned with [`spawn_with_id`](LocalPool::spawn_with_id).
+    ids: RefCell<Slab>,
+    /// Allocations of the futures spawned on the pool, to catch double spawns.
+    #[cfg(debug_assertions)]
+    live: RefCell<alloc::collections::BTreeSet<usize>>,
+    #[cfg(feature = "trace-events")]
+    events: RefCell<EventLog>,
+    /// Set by a [`PausePoint`] to stop [`run_pausable`](LocalPool::run_pausable).
+    pause: Arc<AtomicBool>,
+    /// Results collected while the pool is awaited as a future.
+    awaited: alloc::vec::Vec<Ret>,
+    observer: RefCell<PollObserver<'a>>,
+    #[cfg(feature = "metrics")]
+    metrics: Cell<PoolMetrics>,
 }
 
 
 #[derive(Clone)]
-pub struct Spawner<'a, Ret> {
-    tx: Weak<ArrayQueue<LocalFutureObj<'a, Ret>>>,
+pub struct Spawner<'a, Ret, Q = BusyQueue<Task<'a, Ret>>>
+    where Q: TaskQueue<Task<'a, Ret>> {
+    shared: Arc<Shared<'a, Ret, Q>>,
 }
 
+/// State shared by a pool and all of its spawners.
+///
+/// Memory orderings:
+/// - `shutdown` is stored with `Release` when the pool is dropped and loaded with
+///   `Acquire` by spawners, so a spawner that sees it also sees the drained queue and
+///   rejects the task. A push racing the drop stays queued until the last handle is gone.
+/// - `draining` follows the same `Release`/`Acquire` protocol; a spawn racing
+///   [`begin_drain`](LocalPool::begin_drain) may still be accepted and is run.
+/// - `slots` counts the tasks owned by `queue`, including the ones the pool popped to
+///   poll, and `first_slots` does the same for `first`. A task holds its slot until it
+///   finishes, so putting it back cannot fail even if spawners pushed in the meantime.
+///   Only the count matters, so it is `Relaxed`.
+/// - The counters are statistics and use `Relaxed`. `completed` and `cancelled` are only
+///   written by the pool, so a plain load and store is enough to update them.
+/// - The per-task cancellation flags owned by [`TaskGuard`] are stored with `Release`
+///   and loaded with `Acquire` before the task is polled.
+#[derive(Debug)]
+struct Shared<'a, Ret, Q> {
+    queue: Q,
+    slots: AtomicUsize,
+    /// Priority tasks, polled ahead of `queue`.
+    first: Q,
+    first_slots: AtomicUsize,
+    shutdown: AtomicBool,
+    draining: AtomicBool,
+    spawned: AtomicUsize,
+    completed: AtomicUsize,
+    cancelled: AtomicUsize,
+    _task: PhantomData<Task<'a, Ret>>,
+}
+
+impl<'a, Ret, Q: TaskQueue<Task<'a, Ret>>> Shared<'a, Ret, Q> {
+    fn new(queue: Q, first: Q) -> Self {
+        Self {
+            queue,
+            slots: AtomicUsize::new(0),
+            first,
+            first_slots: AtomicUsize::new(0),
+            shutdown: AtomicBool::new(false),
+            draining: AtomicBool::new(false),
+            spawned: AtomicUsize::new(0),
+            completed: AtomicUsize::new(0),
+            cancelled: AtomicUsize::new(0),
+            _task: PhantomData,
+        }
+    }
 
-impl<'a> Spawner<'a, ()> {
-    pub fn spawn<F>(&self, f: F) -> Result<(), SpawnError>
-        where F: UnsafeFutureObj<'a, ()> + Send {
-        let tx = self.tx.upgrade().ok_or(SpawnError::shutdown())?;
-        tx.push(LocalFutureObj::new(f)).expect("Queue full");
+    /// The priority queue if `first`, otherwise the normal one, with its slot count.
+    fn tier(&self, first: bool) -> (&Q, &AtomicUsize) {
+        if first { (&self.first, &self.first_slots) } else { (&self.queue, &self.slots) }
+    }
+
+    /// Claims a slot for `task` and queues it, growing the queue if it can, and handing
+    /// the task back if the queue is full.
+    fn push(&self, task: Task<'a, Ret>, first: bool) -> Result<(), Task<'a, Ret>> {
+        if !self.reserve(first) {
+            return Err(task);
+        }
+        self.enqueue(task, first);
         Ok(())
     }
+
+    /// Claims a slot in the queue, growing it if it can, and fails if it is full.
+    fn reserve(&self, first: bool) -> bool {
+        let (queue, slots) = self.tier(first);
+        while slots.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| (n < queue.capacity()).then_some(n + 1)).is_err() {
+            if !queue.grow() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Queues a new task for the slot [`reserve`](Shared::reserve) claimed.
+    fn enqueue(&self, task: Task<'a, Ret>, first: bool) {
+        self.requeue(task, first);
+        self.spawned.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Puts back a task that already holds a slot.
+    fn requeue(&self, task: Task<'a, Ret>, first: bool) {
+        // cannot fail: there are never more tasks holding a slot than the capacity
+        let pushed = self.tier(first).0.push(task).is_ok();
+        debug_assert!(pushed, "task queue over capacity; a task was lost");
+    }
+
+    /// Frees the slot of a task that finished.
+    fn release(&self, first: bool) {
+        self.tier(first).1.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// How many more tasks `push` accepts into the normal queue before it has to grow.
+    fn remaining(&self) -> usize {
+        self.queue.capacity().saturating_sub(self.slots.load(Ordering::Relaxed))
+    }
+
+    /// Frees every slot, including those of tasks that panicked while popped.
+    fn reset_slots(&self) {
+        self.slots.store(0, Ordering::Relaxed);
+        self.first_slots.store(0, Ordering::Relaxed);
+    }
+
+    /// Pushes a task from a spawner, unless the pool is gone or full. Both are reported
+    /// as a shutdown, the only error the futures [`Spawn`] trait has.
+    fn submit(&self, task: Task<'a, Ret>, first: bool) -> Result<(), SpawnError> {
+        if self.is_closed() {
+            return Err(SpawnError::shutdown());
+        }
+        self.push(task, first).map_err(|_| SpawnError::shutdown())
+    }
+
+    /// Whether the pool is gone or draining, and so takes no more tasks from spawners.
+    fn is_closed(&self) -> bool {
+        self.shutdown.load(Ordering::Acquire) || self.draining.load(Ordering::Acquire)
+    }
+
+    /// Counts a task leaving the pool.
+    fn record(&self, step: &Step<Ret>) {
+        let counter = match step {
+            Step::Ready(_) => &self.completed,
+            Step::Cancelled => &self.cancelled,
+            Step::Pending | Step::Skipped => return,
+        };
+        counter.store(counter.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> PoolStats {
+        PoolStats {
+            spawned: self.spawned.load(Ordering::Relaxed),
+            completed: self.completed.load(Ordering::Relaxed),
+            cancelled: self.cancelled.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Task counters of a busy pool, see [`stats`](LocalPool::stats).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PoolStats {
+    /// Tasks spawned, through the pool or any of its spawners.
+    pub spawned: usize,
+    /// Tasks that ran to completion.
+    pub completed: usize,
+    /// Tasks dropped because their [`TaskGuard`] was dropped.
+    pub cancelled: usize,
+}
+
+/// Returned by [`append`](LocalPool::append) when some tasks did not fit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppendError {
+    /// How many tasks were left in the other pool.
+    pub left: usize,
+}
+
+impl core::fmt::Display for AppendError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "pool full, {} tasks left behind", self.left)
+    }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for AppendError {}
 
-impl Spawn for Spawner<'static, ()> {
-    fn spawn_obj(&self, future: FutureObj<'static, ()>) -> Result<(), SpawnError> {
-        let tx = self.tx.upgrade().ok_or(SpawnError::shutdown())?;
-        tx.push(future.into()).expect("Queue full");
+/// Scheduling counters of a busy pool, see [`metrics`](LocalPool::metrics).
+///
+/// Unlike [`PoolStats`], these describe the work the pool itself does, and are only
+/// kept with the `metrics` feature.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PoolMetrics {
+    /// Task polls made, not counting cancelled or skipped tasks.
+    pub polls: usize,
+    /// Tasks that ran to completion.
+    pub completed: usize,
+    /// Times a task was pushed back onto its queue after a visit left it pending.
+    pub requeued: usize,
+    /// The most tasks the pool held at the start of a poll.
+    pub max_depth: usize,
+}
+
+/// A task as stored in the queues of a busy pool, opaque to [`TaskQueue`] backends.
+pub struct Task<'a, Ret> {
+    future: LocalFutureObj<'a, Ret>,
+    /// Created lazily on first poll unless the pool uses [`WakerStrategy::Busy`].
+    wake: Option<Arc<TaskWake>>,
+    /// Set by a [`TaskGuard`] when the task should be dropped instead of polled.
+    cancelled: Option<Arc<AtomicBool>>,
+    id: Option<TaskId>,
+    /// Whether the pool's middleware has been applied; tasks pushed by spawners get it
+    /// when the pool first pops them.
+    wrapped: bool,
+    #[cfg(debug_assertions)]
+    addr: Option<usize>,
+}
+
+/// A future already turned into its raw pointer, still dropped the way `F` drops it.
+#[cfg(debug_assertions)]
+struct Traced<'a, Ret, F> {
+    ptr: *mut (dyn Future<Output = Ret> + 'a),
+    _future: core::marker::PhantomData<F>,
+}
+
+// SAFETY: `ptr` came from `F::into_raw` and is released through `F::drop`
+#[cfg(debug_assertions)]
+unsafe impl<'a, Ret: 'a, F: UnsafeFutureObj<'a, Ret>> UnsafeFutureObj<'a, Ret> for Traced<'a, Ret, F> {
+    fn into_raw(self) -> *mut (dyn Future<Output = Ret> + 'a) {
+        self.ptr
+    }
+
+    unsafe fn drop(ptr: *mut (dyn Future<Output = Ret> + 'a)) {
+        F::drop(ptr)
+    }
+}
+
+/// What happened when the pool visited a task.
+enum Step<Ret> {
+    Ready(Ret),
+    Pending,
+    /// The task was not woken, so it was not polled.
+    Skipped,
+    /// The task was cancelled and must be dropped.
+    Cancelled,
+}
+
+/// Cancels its task when dropped.
+///
+/// Returned by [`spawn_guarded`](LocalPool::spawn_guarded). The pool drops the task
+/// the next time it visits it, without polling it again.
+#[derive(Debug)]
+pub struct TaskGuard {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+}
+
+impl<Ret> core::fmt::Debug for Task<'_, Ret> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Task")
+            .field("future", &self.future)
+            .field("wake", &self.wake)
+            .field("cancelled", &self.cancelled)
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+impl<'a, Ret> Task<'a, Ret> {
+    fn new(future: LocalFutureObj<'a, Ret>) -> Self {
+        Self {
+            future,
+            wake: None,
+            cancelled: None,
+            id: None,
+            wrapped: false,
+            #[cfg(debug_assertions)]
+            addr: None,
+        }
+    }
+
+    /// A task whose future already went through the middleware.
+    fn wrapped(future: LocalFutureObj<'a, Ret>) -> Self {
+        Self { wrapped: true, ..Self::new(future) }
+    }
+
+    /// Polls the task unless it was cancelled or the strategy says to skip it.
+    fn poll(&mut self, strategy: WakerStrategy, cx: &mut Context<'_>) -> Step<Ret> {
+        if let Some(cancelled) = &self.cancelled {
+            if cancelled.load(Ordering::Acquire) {
+                return Step::Cancelled;
+            }
+        }
+        let ret = if strategy == WakerStrategy::Busy {
+            self.future.poll_unpin(cx)
+        } else {
+            let wake = self.wake.get_or_insert_with(|| TaskWake::new(strategy));
+            // registered before the flag is read, so a wake-up in between still reaches `cx`
+            wake.register(cx.waker());
+            if !wake.read_reset() {
+                return Step::Skipped;
+            }
+            let waker = futures::task::waker_ref(wake);
+            let mut cx = Context::from_waker(&waker);
+            self.future.poll_unpin(&mut cx)
+        };
+        match ret {
+            Poll::Ready(ret) => Step::Ready(ret),
+            Poll::Pending => Step::Pending,
+        }
+    }
+
+    /// Polls the task unless it was cancelled, woken or not, with a waker whose
+    /// wake-ups are recorded in the returned flag as well as the task's own waker.
+    fn poll_observed(&mut self, strategy: WakerStrategy) -> (Step<Ret>, Arc<SingleWake>) {
+        let woken = Arc::new(SingleWake::new());
+        if let Some(cancelled) = &self.cancelled {
+            if cancelled.load(Ordering::Acquire) {
+                return (Step::Cancelled, woken);
+            }
+        }
+        let task = match strategy {
+            WakerStrategy::Busy => None,
+            _ => {
+                let wake = self.wake.get_or_insert_with(|| TaskWake::new(strategy));
+                // this poll uses up any wake-up the task had pending
+                wake.read_reset();
+                Some(wake.clone())
+            }
+        };
+        let waker = waker_from_arc(Arc::new(StepWake { woken: woken.clone(), task }));
+        let mut cx = Context::from_waker(&waker);
+        let step = match self.future.poll_unpin(&mut cx) {
+            Poll::Ready(ret) => Step::Ready(ret),
+            Poll::Pending => Step::Pending,
+        };
+        (step, woken)
+    }
+}
+
+/// Releases the slot and id of a popped task whose poll panicked; forgotten once the
+/// poll returns.
+struct Popped<'p, 'a, Ret, Q: TaskQueue<Task<'a, Ret>>> {
+    pool: &'p LocalPool<'a, Ret, Q>,
+    first: bool,
+    id: Option<TaskId>,
+    #[cfg(debug_assertions)]
+    addr: Option<usize>,
+}
+
+impl<'a, Ret, Q: TaskQueue<Task<'a, Ret>>> Drop for Popped<'_, 'a, Ret, Q> {
+    fn drop(&mut self) {
+        self.pool.shared.release(self.first);
+        if let Some(id) = self.id {
+            self.pool.ids.borrow_mut().remove(id);
+        }
+        #[cfg(debug_assertions)]
+        if let Some(addr) = self.addr {
+            self.pool.live.borrow_mut().remove(&addr);
+        }
+    }
+}
+
+/// The waker of a task polled by [`step`](LocalPool::step).
+struct StepWake {
+    woken: Arc<SingleWake>,
+    /// The task's own waker, so the rest of the pool still sees the wake-up.
+    task: Option<Arc<TaskWake>>,
+}
+
+impl SimpleWaker for StepWake {
+    fn wake(&self) {
+        self.woken.wake();
+        if let Some(task) = &self.task {
+            futures::task::ArcWake::wake_by_ref(task);
+        }
+    }
+}
+
+/// What [`step`](LocalPool::step) did with the task it popped.
+#[derive(Debug)]
+pub enum StepOutcome<Ret> {
+    /// The pool had no tasks.
+    Empty,
+    /// The task completed.
+    Ready {
+        /// The task's id, if it has one, see [`spawn_with_id`](LocalPool::spawn_with_id).
+        id: Option<TaskId>,
+        /// The task's position in the current round, see [`step`](LocalPool::step).
+        position: usize,
+        output: Ret,
+    },
+    /// The task is still pending and went back to the end of its queue.
+    Pending {
+        /// The task's id, if it has one, see [`spawn_with_id`](LocalPool::spawn_with_id).
+        id: Option<TaskId>,
+        /// The task's position in the current round, see [`step`](LocalPool::step).
+        position: usize,
+        /// Set whenever the task is woken from now on, including during the poll.
+        woken: Arc<SingleWake>,
+    },
+    /// The task had been cancelled and was dropped without being polled.
+    Cancelled {
+        /// The task's id, if it has one, see [`spawn_with_id`](LocalPool::spawn_with_id).
+        id: Option<TaskId>,
+        /// The task's position in the current round, see [`step`](LocalPool::step).
+        position: usize,
+    },
+}
+
+
+impl<'a, Ret, Q: TaskQueue<Task<'a, Ret>>> Spawner<'a, Ret, Q> {
+    /// Spawn `f` onto the pool, handing it back if the pool turns it away.
+    ///
+    /// A full queue is reported as [`Full`](SpawnErrorKind::Full), so `f` can be
+    /// resubmitted once the queue has room; only a pool that is gone or draining reports
+    /// [`Shutdown`](SpawnErrorKind::Shutdown).
+    ///
+    /// ```rust
+    /// use minimal_executor::{BusyLocalPool, SpawnErrorKind};
+    ///
+    /// let mut pool = BusyLocalPool::new(1);
+    /// let spawner = pool.spawner();
+    /// spawner.spawn(Box::pin(async { 1 })).unwrap();
+    /// let err = spawner.spawn(Box::pin(async { 2 })).unwrap_err();
+    /// assert_eq!(err.kind, SpawnErrorKind::Full);
+    ///
+    /// assert_eq!(pool.run(), vec![1]);
+    /// spawner.spawn(err.into_future()).unwrap();
+    /// assert_eq!(pool.run(), vec![2]);
+    /// ```
+    pub fn spawn<F>(&self, f: F) -> Result<(), TrySpawnError<F>>
+        where F: UnsafeFutureObj<'a, Ret> + Send {
+        self.submit(f, false)
+    }
+
+    /// Like [`spawn`](Spawner::spawn), but the task is polled ahead of the normal ones,
+    /// see [`LocalPool::spawn_priority`].
+    pub fn spawn_priority<F>(&self, f: F) -> Result<(), TrySpawnError<F>>
+        where F: UnsafeFutureObj<'a, Ret> + Send {
+        self.submit(f, true)
+    }
+
+    /// Same as [`spawn`](Spawner::spawn), which hands `f` back as well.
+    pub fn try_spawn<F>(&self, f: F) -> Result<(), TrySpawnError<F>>
+        where F: UnsafeFutureObj<'a, Ret> + Send {
+        self.spawn(f)
+    }
+
+    fn submit<F>(&self, f: F, first: bool) -> Result<(), TrySpawnError<F>>
+        where F: UnsafeFutureObj<'a, Ret> + Send {
+        if self.shared.is_closed() {
+            return Err(TrySpawnError::new(SpawnErrorKind::Shutdown, f));
+        }
+        if !self.shared.reserve(first) {
+            return Err(TrySpawnError::new(SpawnErrorKind::Full, f));
+        }
+        self.shared.enqueue(Task::new(LocalFutureObj::new(f)), first);
         Ok(())
     }
+
+    /// How many more tasks can be spawned before the pool's queue is full, or `None`
+    /// once the pool is gone or draining, see [`LocalPool::remaining_capacity`].
+    pub fn remaining_capacity(&self) -> Option<usize> {
+        if self.shared.is_closed() {
+            return None;
+        }
+        Some(self.shared.remaining())
+    }
+}
+
+
+/// [`SpawnError`] cannot tell a full queue from a closed pool, so `spawn_obj` reports
+/// both as a shutdown; use [`Spawner::spawn`] to tell them apart.
+impl<Q: TaskQueue<Task<'static, ()>>> Spawn for Spawner<'static, (), Q> {
+    fn spawn_obj(&self, future: FutureObj<'static, ()>) -> Result<(), SpawnError> {
+        self.shared.submit(Task::new(future.into()), false)
+    }
+}
+
+
+/// How [`run_pausable`](LocalPool::run_pausable) returned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunOutcome<Ret> {
+    /// The pool is empty; holds every result of this run.
+    Done(alloc::vec::Vec<Ret>),
+    /// A task requested a pause; holds the results so far.
+    Paused(alloc::vec::Vec<Ret>),
+}
+
+/// Pauses the pool that created it, see [`pause_point`](LocalPool::pause_point).
+///
+/// The first poll requests the pause and yields; the task continues once the pool
+/// is run again.
+#[derive(Debug)]
+pub struct PausePoint {
+    pause: Arc<AtomicBool>,
+    requested: bool,
+}
+
+impl Future for PausePoint {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.requested {
+            return Poll::Ready(());
+        }
+        self.requested = true;
+        self.pause.store(true, Ordering::Release);
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// The outcome of one [`run_once`](LocalPool::run_once) sweep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunTick<Ret> {
+    /// Results of the tasks that completed during the sweep.
+    pub completed: alloc::vec::Vec<Ret>,
+    /// Whether any task completed.
+    pub made_progress: bool,
+    /// Tasks still queued after the sweep.
+    pub remaining: usize,
 }
 
+/// Awaiting the pool runs it to completion with the caller's context, resolving to
+/// every result once the pool is empty.
+impl<'a, Ret: 'a, Q: TaskQueue<Task<'a, Ret>>> Future for &mut LocalPool<'a, Ret, Q> {
+    type Output = alloc::vec::Vec<Ret>;
 
-impl<'a, Ret> LocalPool<'a, Ret> {
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let pool = &mut **self.get_mut();
+        let mut awaited = core::mem::take(&mut pool.awaited);
+        match pool.poll_drain(cx, |r| awaited.push(r)) {
+            Poll::Ready(()) => Poll::Ready(awaited),
+            Poll::Pending => {
+                pool.awaited = awaited;
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A one-line summary for logs, e.g. `pool: 3/256 tasks, 1024 completed`.
+impl<'a, Ret: 'a, Q: TaskQueue<Task<'a, Ret>>> core::fmt::Display for LocalPool<'a, Ret, Q> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        crate::write_summary(f, self.len(), Some(self.capacity()), self.stats().completed, None)
+    }
+}
+
+impl<'a, Ret, Q: TaskQueue<Task<'a, Ret>>> Drop for LocalPool<'a, Ret, Q> {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::Release);
+        while self.shared.queue.pop().is_some() {}
+        while self.shared.first.pop().is_some() {}
+    }
+}
+
+impl<'a, Ret: 'a> LocalPool<'a, Ret> {
     /// Create a new, empty pool of tasks.
     pub fn new(cap: usize) -> Self {
+        Self::with_strategy(cap, WakerStrategy::Busy)
+    }
+
+    /// Create a new, empty pool whose tasks are woken according to `strategy`.
+    pub fn with_strategy(cap: usize, strategy: WakerStrategy) -> Self {
+        Self::with_queues(BusyQueue::fixed(cap), BusyQueue::fixed(cap), strategy)
+    }
+
+    /// Create a new, empty pool that starts with room for `initial` tasks and doubles
+    /// its capacity whenever a spawn would not fit, up to `max` tasks.
+    ///
+    /// Spawns only fail with a [`SpawnError`] once `max` is reached.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `initial` is zero or larger than `max`.
+    pub fn with_growth(initial: usize, max: usize) -> Self {
+        Self::with_queues(BusyQueue::growable(initial, max), BusyQueue::growable(initial, max), WakerStrategy::Busy)
+    }
+
+    /// Create a pool running a fixed set of borrowed futures, without boxing them.
+    ///
+    /// The futures are polled in place through the slice, so the tasks themselves are
+    /// never boxed. The pool still makes a fixed set of allocations however many tasks
+    /// it runs: the shared state, its two task queues (sized to the slice) and the pause
+    /// flag. Debug builds also record each future's address to catch double spawns, and
+    /// with `trace-events` the event log grows up to its bounded capacity.
+    ///
+    /// ```rust
+    /// use core::future::Future;
+    /// use core::pin::{pin, Pin};
+    /// use minimal_executor::BusyLocalPool;
+    ///
+    /// let (a, b) = (pin!(async { 1 }), pin!(async { 2 }));
+    /// let mut tasks: [Pin<&mut dyn Future<Output = i32>>; 2] = [a, b];
+    /// let mut pool = BusyLocalPool::from_slice(&mut tasks);
+    /// assert_eq!(pool.run_fold(0, |sum, i| sum + i), 3);
+    /// ```
+    pub fn from_slice(tasks: &'a mut [Pin<&'a mut (dyn Future<Output = Ret> + 'a)>]) -> Self {
+        let mut pool = Self::new(tasks.len().max(1));
+        for task in tasks {
+            // the queue is sized to fit every task
+            pool.spawn_or_panic(task);
+        }
+        pool
+    }
+}
+
+impl<'a, Ret: 'a, Q: TaskQueue<Task<'a, Ret>>> LocalPool<'a, Ret, Q> {
+    /// Create a new, empty pool on custom queue backends: `queue` for the tasks spawned
+    /// with [`spawn`](LocalPool::spawn) and `first` for the priority ones.
+    pub fn with_queues(queue: Q, first: Q, strategy: WakerStrategy) -> Self {
         Self {
-            pool: Arc::new(ArrayQueue::new(cap)),
+            shared: Arc::new(Shared::new(queue, first)),
+            strategy,
+            fairness: None,
+            starved: Cell::new(0),
+            round: Cell::new((0, 0)),
+            visited: Cell::new(0),
+            middleware: RefCell::new(Middleware::new()),
+            ids: RefCell::default(),
+            #[cfg(debug_assertions)]
+            live: RefCell::default(),
+            #[cfg(feature = "trace-events")]
+            events: RefCell::default(),
+            pause: Arc::new(AtomicBool::new(false)),
+            awaited: alloc::vec::Vec::new(),
+            observer: RefCell::new(PollObserver::new()),
+            #[cfg(feature = "metrics")]
+            metrics: Cell::default(),
         }
     }
 
-    pub fn spawner(&self) -> Spawner<'a, Ret> {
+    /// Install a spawn middleware layer, applied to every task spawned on the pool afterwards.
+    ///
+    /// The layer receives the task and returns the (possibly wrapped) future that is
+    /// actually stored, e.g. to add timing or tracing. Layers compose: each one wraps
+    /// the result of the layers installed before it.
+    ///
+    /// Tasks pushed through a [`Spawner`] are wrapped when the pool first takes them
+    /// out of the queue.
+    pub fn with_middleware<F>(mut self, layer: F) -> Self
+        where F: FnMut(LocalFutureObj<'a, Ret>) -> LocalFutureObj<'a, Ret> + 'a {
+        self.middleware.get_mut().push(layer);
+        self
+    }
+
+    /// Keep priority tasks from starving the normal ones: once `ratio - 1` sweeps in a
+    /// row returned before polling a normal task, the next sweep polls the normal tasks
+    /// first.
+    ///
+    /// This only affects [`poll_though`](LocalPool::poll_though) and the methods built
+    /// on it; [`poll_once`](LocalPool::poll_once) already visits every task once per round.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ratio` is zero.
+    pub fn with_fairness(mut self, ratio: usize) -> Self {
+        assert!(ratio > 0, "fairness ratio must be nonzero");
+        self.fairness = Some(ratio);
+        self
+    }
+
+    pub fn strategy(&self) -> WakerStrategy {
+        self.strategy
+    }
+
+    /// The most recent scheduling events, oldest first.
+    ///
+    /// Every task spawned on the pool gets a [`TaskId`] while this feature is on; tasks
+    /// pushed through a [`Spawner`] have none and are not traced.
+    #[cfg(feature = "trace-events")]
+    pub fn events(&self) -> alloc::vec::Vec<Event> {
+        self.events.borrow().snapshot()
+    }
+
+    #[cfg(feature = "trace-events")]
+    pub fn clear_events(&mut self) {
+        self.events.borrow_mut().clear();
+    }
+
+    /// Stop accepting tasks from spawners while the queued tasks keep running.
+    ///
+    /// Spawners get a shutdown [`SpawnError`] from then on, so [`run`](LocalPool::run)
+    /// returns once the work already accepted is done.
+    pub fn begin_drain(&mut self) {
+        self.shared.draining.store(true, Ordering::Release);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.shared.draining.load(Ordering::Acquire)
+    }
+
+    /// Take every unfinished task out of the pool, e.g. to re-spawn them on another
+    /// executor or count what was abandoned at shutdown.
+    ///
+    /// Tasks cancelled through a [`TaskGuard`] are dropped instead of returned. The
+    /// returned futures already have the pool's middleware applied.
+    ///
+    /// ```rust
+    /// use minimal_executor::BusyLocalPool;
+    ///
+    /// let mut pool: BusyLocalPool<'_, ()> = BusyLocalPool::new(4);
+    /// pool.spawn(Box::pin(futures::future::pending())).unwrap();
+    /// assert!(pool.try_run_one().is_pending());
+    /// assert_eq!(pool.drain().len(), 1);
+    /// assert!(pool.run().is_empty());
+    /// ```
+    pub fn drain(&mut self) -> alloc::vec::Vec<LocalFutureObj<'a, Ret>> {
+        let mut drained = alloc::vec::Vec::with_capacity(self.len());
+        while let Some(task) = self.pop(true).or_else(|| self.pop(false)) {
+            self.untrack(&task);
+            match &task.cancelled {
+                Some(cancelled) if cancelled.load(Ordering::Acquire) => {
+                    self.shared.record(&Step::Cancelled);
+                }
+                _ => drained.push(task.future),
+            }
+        }
+        self.shared.reset_slots();
+        self.end_round();
+        drained
+    }
+
+    /// Move every unfinished task of `other` into this pool, the counterpart of
+    /// [`drain`](LocalPool::drain) for consolidating two pools.
+    ///
+    /// Tasks keep their queue, their wake-up state and their [`TaskGuard`], and count as
+    /// spawned on this pool. Ids handed out by `other` are not valid here: tasks that had
+    /// one get a new one. If this pool fills up, as many tasks as fit are moved and the
+    /// rest stay in `other`, in their order, with the count reported in the error.
+    ///
+    /// ```rust
+    /// use minimal_executor::{AppendError, BusyLocalPool};
+    ///
+    /// let mut small = BusyLocalPool::new(2);
+    /// let mut big = BusyLocalPool::new(4);
+    /// for i in 0..3 {
+    ///     big.spawn(Box::pin(async move { i })).unwrap();
+    /// }
+    /// assert_eq!(small.append(&mut big), Err(AppendError { left: 1 }));
+    /// assert_eq!(small.run(), vec![0, 1]);
+    /// assert_eq!(big.run(), vec![2]);
+    /// ```
+    pub fn append(&mut self, other: &mut Self) -> Result<(), AppendError> {
+        let mut left = 0;
+        for first in [true, false] {
+            for _ in 0..other.queue(first).len() {
+                let Some(task) = other.pop(first) else { break };
+                if let Some(cancelled) = &task.cancelled {
+                    if cancelled.load(Ordering::Acquire) {
+                        other.untrack(&task);
+                        other.shared.record(&Step::Cancelled);
+                        other.shared.release(first);
+                        continue;
+                    }
+                }
+                if !self.shared.reserve(first) {
+                    other.shared.requeue(task, first);
+                    left += 1;
+                    continue;
+                }
+                other.untrack(&task);
+                other.shared.release(first);
+                let task = self.adopt(task);
+                self.shared.enqueue(task, first);
+            }
+        }
+        other.end_round();
+        match left {
+            0 => Ok(()),
+            left => Err(AppendError { left }),
+        }
+    }
+
+    /// How many tasks were spawned, completed, and cancelled so far.
+    pub fn stats(&self) -> PoolStats {
+        self.shared.stats()
+    }
+
+    /// How much scheduling work the pool has done so far.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> PoolMetrics {
+        self.metrics.get()
+    }
+
+    pub fn spawner(&self) -> Spawner<'a, Ret, Q> {
         Spawner {
-            tx: Arc::downgrade(&self.pool),
+            shared: self.shared.clone(),
         }
     }
-    pub fn spawn<F>(&mut self, f: F)
+    /// Number of tasks in the pool, including the ones pushed by spawners.
+    pub fn len(&self) -> usize {
+        self.shared.first.len() + self.shared.queue.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// How many tasks the queue behind [`spawn`](LocalPool::spawn) holds; priority tasks
+    /// have a queue of their own.
+    ///
+    /// For a pool created with [`with_growth`](LocalPool::with_growth) this is the
+    /// current capacity, which grows on demand up to the max.
+    pub fn capacity(&self) -> usize {
+        self.shared.queue.capacity()
+    }
+    /// How many more tasks [`spawn`](LocalPool::spawn) accepts before the queue is full,
+    /// counting the ones pushed by spawners.
+    ///
+    /// ```rust
+    /// use minimal_executor::BusyLocalPool;
+    ///
+    /// let mut pool = BusyLocalPool::new(4);
+    /// pool.spawn(Box::pin(async {})).unwrap();
+    /// assert_eq!((pool.capacity(), pool.remaining_capacity()), (4, 3));
+    /// ```
+    pub fn remaining_capacity(&self) -> usize {
+        self.shared.remaining()
+    }
+    /// Spawn a task, failing with [`SpawnErrorKind::Full`] if the queue is full.
+    ///
+    /// The error hands the future back, so callers can apply backpressure by running
+    /// the pool before retrying with it.
+    ///
+    /// ```rust
+    /// use minimal_executor::{BusyLocalPool, SpawnErrorKind};
+    ///
+    /// let mut pool = BusyLocalPool::new(1);
+    /// pool.spawn(Box::pin(async { 1 })).unwrap();
+    /// let err = pool.spawn(Box::pin(async { 2 })).unwrap_err();
+    /// assert_eq!(err.kind, SpawnErrorKind::Full);
+    /// assert_eq!(pool.run(), vec![1]);
+    /// pool.spawn(err.into_future()).unwrap();
+    /// assert_eq!(pool.run(), vec![2]);
+    /// ```
+    pub fn spawn<F>(&mut self, f: F) -> Result<(), TrySpawnError<F>>
+        where F: UnsafeFutureObj<'a, Ret> {
+        let f = self.reserve(f, false)?;
+        let task = self.new_task(f);
+        self.shared.enqueue(task, false);
+        Ok(())
+    }
+    /// Like [`spawn`](LocalPool::spawn), but panics if the queue is full.
+    pub fn spawn_or_panic<F>(&mut self, f: F)
         where F: UnsafeFutureObj<'a, Ret> {
-        self.pool.push(LocalFutureObj::new(f)).expect("Queue full");
+        self.spawn(f).expect("Queue full")
     }
+    /// Spawn a task that is cancelled when the returned [`TaskGuard`] is dropped.
+    pub fn spawn_guarded<F>(&mut self, f: F) -> Result<TaskGuard, TrySpawnError<F>>
+        where F: UnsafeFutureObj<'a, Ret> {
+        let f = self.reserve(f, false)?;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut task = self.new_task(f);
+        task.cancelled = Some(cancelled.clone());
+        self.shared.enqueue(task, false);
+        Ok(TaskGuard { cancelled })
+    }
+    /// Spawn a task and return an id that stays valid until the task finishes.
+    ///
+    /// Ids of finished tasks are reused, so the id table only grows with the number of
+    /// such tasks in flight at once.
+    pub fn spawn_with_id<F>(&mut self, f: F) -> Result<TaskId, TrySpawnError<F>>
+        where F: UnsafeFutureObj<'a, Ret> {
+        let f = self.reserve(f, false)?;
+        let mut task = self.new_task(f);
+        let id = *task.id.get_or_insert_with(|| self.ids.borrow_mut().insert());
+        self.shared.enqueue(task, false);
+        Ok(id)
+    }
+    /// Whether the task with `id` is still in the pool.
+    pub fn contains(&self, id: TaskId) -> bool {
+        self.ids.borrow().contains(id)
+    }
+    /// Call `f` after every poll of a task, with the task's id and what the poll
+    /// returned, e.g. to emit tracing spans or count pending polls.
+    ///
+    /// Fires for every poll, including those that leave the task pending and put it
+    /// back in the queue; tasks that were not polled, because they were not woken or
+    /// were cancelled, are not reported. Tasks without an id get one on their first
+    /// observed poll, which stays valid until the task finishes. Replaces any observer
+    /// set before.
+    ///
+    /// ```rust
+    /// use minimal_executor::{BusyLocalPool, PollState};
+    /// use std::cell::RefCell;
+    ///
+    /// let polls = RefCell::new(Vec::new());
+    /// let mut pool = BusyLocalPool::new(4);
+    /// pool.set_poll_observer(|_, state| polls.borrow_mut().push(state));
+    /// pool.spawn(Box::pin(async {
+    ///     futures::pending!();
+    /// })).unwrap();
+    /// pool.run();
+    /// drop(pool);
+    /// assert_eq!(polls.into_inner(), [PollState::Pending, PollState::Ready]);
+    /// ```
+    pub fn set_poll_observer<F: FnMut(TaskId, PollState) + 'a>(&mut self, f: F) {
+        self.observer.get_mut().set(f);
+    }
+    /// The ids of the tasks still in the pool, whether queued or waiting for a wake-up.
+    ///
+    /// Only tasks spawned with [`spawn_with_id`](LocalPool::spawn_with_id) have an id,
+    /// or every task spawned on the pool itself with the `trace-events` feature. Handy
+    /// for finding the tasks a stuck pool is waiting on.
+    ///
+    /// ```rust
+    /// use minimal_executor::BusyLocalPool;
+    ///
+    /// let mut pool = BusyLocalPool::new(4);
+    /// let done = pool.spawn_with_id(Box::pin(async {})).unwrap();
+    /// let stuck = pool.spawn_with_id(Box::pin(futures::future::pending())).unwrap();
+    /// assert_eq!(pool.pending_ids(), vec![done, stuck]);
+    /// let _ = pool.poll_once();
+    /// assert_eq!(pool.pending_ids(), vec![stuck]);
+    /// ```
+    pub fn pending_ids(&self) -> alloc::vec::Vec<TaskId> {
+        self.ids.borrow().live().collect()
+    }
+    /// Number of slots in the id table, live or free.
+    pub fn id_slots(&self) -> usize {
+        self.ids.borrow().slots()
+    }
+    /// Spawn a latency-critical task that is polled before every task spawned with
+    /// [`spawn`](LocalPool::spawn).
+    ///
+    /// Each sweep of [`poll_though`](LocalPool::poll_though) polls every priority task
+    /// before any normal one, so a steady stream of completing priority tasks can keep
+    /// the normal ones waiting; see [`with_fairness`](LocalPool::with_fairness). The
+    /// priority queue has the same capacity as the normal one.
+    pub fn spawn_priority<F>(&mut self, f: F) -> Result<(), TrySpawnError<F>>
+        where F: UnsafeFutureObj<'a, Ret> {
+        let f = self.reserve(f, true)?;
+        let task = self.new_task(f);
+        self.shared.enqueue(task, true);
+        Ok(())
+    }
+    /// Same as [`spawn_priority`](LocalPool::spawn_priority).
+    #[deprecated(note = "use spawn_priority")]
+    pub fn spawn_first<F>(&mut self, f: F) -> Result<(), TrySpawnError<F>>
+        where F: UnsafeFutureObj<'a, Ret> {
+        self.spawn_priority(f)
+    }
+    /// Spawn a fused future, skipping it if it has already terminated.
+    ///
+    /// A terminated future usually means a completed one was accidentally reused: debug
+    /// builds panic on it, release builds skip it and return `Ok(false)`. A full queue
+    /// is reported as an error instead, handing the future back as
+    /// [`spawn`](LocalPool::spawn) does.
+    pub fn spawn_checked<F>(&mut self, f: F) -> Result<bool, TrySpawnError<F>>
+        where F: FusedFuture<Output = Ret> + 'a {
+        debug_assert!(!f.is_terminated(), "spawned a future that already terminated");
+        if f.is_terminated() {
+            return Ok(false);
+        }
+        let f = self.reserve(f, false)?;
+        let task = self.new_task(Box::pin(f));
+        self.shared.enqueue(task, false);
+        Ok(true)
+    }
+    /// Like [`run`](LocalPool::run), but returns a [`StuckError`](crate::StuckError)
+    /// once `max_idle_rounds` passes in a row completed no task, instead of spinning
+    /// forever on tasks that never complete.
+    ///
+    /// See [`PoolExt::run_bounded`](crate::PoolExt::run_bounded).
+    pub fn run_bounded(&mut self, max_idle_rounds: usize) -> Result<alloc::vec::Vec<Ret>, crate::StuckError> {
+        crate::PoolExt::run_bounded(self, max_idle_rounds)
+    }
+
+    /// Like [`run`](LocalPool::run), but hands out the results one at a time as tasks
+    /// complete, so the caller can stop early and leave the rest in the pool.
+    ///
+    /// See [`RunIter`](crate::RunIter).
+    pub fn run_iter(&mut self) -> crate::RunIter<'_, Self> {
+        crate::RunIter::new(self)
+    }
+
     /// Run all tasks in the pool to completion.
     ///
+    /// With the `std` feature, a panicking task drops every task left in the pool as
+    /// the panic unwinds out of this call.
+    ///
     /// ```rust
     ///
     /// use minimal_executor::LocalPool;
@@ -85,18 +1052,99 @@ impl<'a, Ret> LocalPool<'a, Ret> {
     /// The function will block the calling thread until *all* tasks in the pool
     /// are complete, including any spawned while running existing tasks.
     pub fn run(&mut self) -> alloc::vec::Vec<Ret> {
-        let mut results = alloc::vec::Vec::new();
-        loop {
-            let ret = self.poll_once();
+        let results = alloc::vec::Vec::with_capacity(self.len());
+        self.run_fold(results, |mut results, r| {
+            results.push(r);
+            results
+        })
+    }
 
-            // no queued tasks; we may be done
-            match ret {
-                Poll::Pending => {}
-                Poll::Ready(None) => break,
-                Poll::Ready(Some(r)) => { results.push(r); }
-            }
+    /// Run all tasks to completion, folding each result into an accumulator as it
+    /// completes instead of collecting them.
+    ///
+    /// ```rust
+    /// use minimal_executor::BusyLocalPool;
+    ///
+    /// let mut pool = BusyLocalPool::new(4);
+    /// for i in 1..=3 {
+    ///     pool.spawn(Box::pin(async move { i })).unwrap();
+    /// }
+    /// assert_eq!(pool.run_fold(0, |sum, i| sum + i), 6);
+    /// ```
+    pub fn run_fold<B, F: FnMut(B, Ret) -> B>(&mut self, init: B, f: F) -> B {
+        self.drive(init, f, false).0
+    }
+
+    /// Run all tasks to completion, handing each result to `on_result` as it completes.
+    ///
+    /// Nothing is collected, so this does not allocate for the results.
+    ///
+    /// ```rust
+    /// use minimal_executor::BusyLocalPool;
+    ///
+    /// let mut pool = BusyLocalPool::new(4);
+    /// for i in 1..=3 {
+    ///     pool.spawn(Box::pin(async move { i })).unwrap();
+    /// }
+    /// let mut max = 0;
+    /// pool.run_with(|i| max = max.max(i));
+    /// assert_eq!(max, 3);
+    /// ```
+    pub fn run_with<F: FnMut(Ret)>(&mut self, mut on_result: F) {
+        self.run_fold((), |(), r| on_result(r))
+    }
+
+    /// Like [`run`](LocalPool::run), but returns early once a task hits a [`PausePoint`]
+    /// from [`pause_point`](LocalPool::pause_point).
+    ///
+    /// The pool stops after the sweep in which the pause was requested and reports the
+    /// results so far; calling this again resumes where it left off.
+    pub fn run_pausable(&mut self) -> RunOutcome<Ret> {
+        // a pause requested outside of a pausable run has nothing to stop
+        self.pause.store(false, Ordering::Relaxed);
+        match self.drive(alloc::vec::Vec::new(), |mut results, r| {
+            results.push(r);
+            results
+        }, true) {
+            (results, true) => RunOutcome::Paused(results),
+            (results, false) => RunOutcome::Done(results),
         }
-        results
+    }
+
+    /// A future that pauses [`run_pausable`](LocalPool::run_pausable) when a task awaits it.
+    pub fn pause_point(&self) -> PausePoint {
+        PausePoint { pause: self.pause.clone(), requested: false }
+    }
+
+    /// Sweeps until the pool is empty, or until a pause is requested if `pausable`.
+    /// Returns the folded results and whether the run was paused.
+    fn drive<B, F: FnMut(B, Ret) -> B>(&mut self, init: B, mut f: F, pausable: bool) -> (B, bool) {
+        drop_on_panic(self, |pool| {
+            let mut acc = Some(init);
+            let mut paused = false;
+            // build the context once; tasks that are ready on their first poll complete
+            // straight out of the queue without being pushed back
+            poll_fn(|cx| {
+                while !pool.is_empty() {
+                    let polled = pool.sweep(cx, None, |r| {
+                        acc = acc.take().map(|acc| f(acc, r));
+                        true
+                    });
+                    #[cfg(feature = "std")]
+                    if polled == 0 && pool.strategy == WakerStrategy::Parking {
+                        // a full sweep found nothing woken; sleep until a task wakes us
+                        std::thread::park();
+                    }
+                    #[cfg(not(feature = "std"))]
+                    let _ = polled;
+                    if pausable && pool.pause.swap(false, Ordering::Acquire) {
+                        paused = true;
+                        break;
+                    }
+                }
+            });
+            (acc.expect("fold accumulator is always present between results"), paused)
+        }, Self::clear)
     }
 
     /// Runs all tasks and returns after completing one future or until no more progress
@@ -141,41 +1189,509 @@ impl<'a, Ret> LocalPool<'a, Ret> {
         }
     }
 
+    /// Run tasks until `max` of them have completed or none of the rest can make
+    /// progress, returning the results in completion order.
+    ///
+    /// Sits between [`try_run_one`](LocalPool::try_run_one) and [`run`](LocalPool::run):
+    /// it stops at the first [`try_run_one`](LocalPool::try_run_one) that completes
+    /// nothing, so pending tasks make it return early instead of spinning. Unfinished
+    /// tasks stay in the pool.
+    pub fn run_n(&mut self, max: usize) -> alloc::vec::Vec<Ret> {
+        let mut results = alloc::vec::Vec::new();
+        while results.len() < max {
+            match self.try_run_one() {
+                Poll::Ready(r) => results.push(r),
+                Poll::Pending => break,
+            }
+        }
+        results
+    }
+
     pub fn poll_though(&mut self) -> Poll<Option<Ret>> {
-        let len = self.pool.len();
-        if len == 0 {
+        self.poll_sweep(None)
+    }
+
+    /// Like [`poll_though`](LocalPool::poll_though), but every task poll is charged to
+    /// `budget`, and the sweep stops early once the budget is exhausted.
+    pub fn poll_budgeted(&mut self, budget: &PollBudget) -> Poll<Option<Ret>> {
+        self.poll_sweep(Some(budget))
+    }
+
+    /// Pops the next task, polls it exactly once and reports what happened.
+    ///
+    /// Tasks are taken in the order [`poll_once`](LocalPool::poll_once) visits them,
+    /// but each call touches a single task, which is polled whether or not it was
+    /// woken. Stepping the pool by hand makes interleavings of tasks reproducible in
+    /// tests, and the [`woken`](StepOutcome::Pending::woken) flag of a pending task
+    /// shows whether it arranged a wake-up without completing.
+    ///
+    /// A round visits every task queued when it started, priority tasks first, and the
+    /// reported `position` counts from 0 within the round, so it is the task's index
+    /// in the queues at that time. Unlike the id, every task has one. Running, sweeping,
+    /// clearing or draining the pool in between ends the round.
+    ///
+    /// ```rust
+    /// use minimal_executor::{BusyLocalPool, StepOutcome};
+    ///
+    /// let (tx, rx) = futures::channel::oneshot::channel();
+    /// let mut pool = BusyLocalPool::new(4);
+    /// let id = pool.spawn_with_id(Box::pin(async move { rx.await.unwrap() })).unwrap();
+    ///
+    /// let StepOutcome::Pending { woken, .. } = pool.step() else { unreachable!() };
+    /// assert!(!woken.read_reset());
+    /// tx.send(5).unwrap();
+    /// assert!(woken.read_reset());
+    /// assert!(matches!(pool.step(), StepOutcome::Ready { id: Some(i), position: 0, output: 5 } if i == id));
+    /// assert!(matches!(pool.step(), StepOutcome::Empty));
+    /// ```
+    pub fn step(&mut self) -> StepOutcome<Ret> {
+        self.observe_depth();
+        let (mut task, first) = match self.next_task() {
+            Some(next) => next,
+            None => return StepOutcome::Empty,
+        };
+        let strategy = self.strategy;
+        let (step, woken) = self.poll_popped(&mut task, first, |task| task.poll_observed(strategy));
+        self.retire(&mut task, &step, first);
+        let (id, position) = (task.id, self.visited.get() - 1);
+        match step {
+            Step::Ready(output) => StepOutcome::Ready { id, position, output },
+            Step::Cancelled => StepOutcome::Cancelled { id, position },
+            Step::Pending | Step::Skipped => {
+                self.requeue(task, first);
+                StepOutcome::Pending { id, position, woken }
+            }
+        }
+    }
+
+    /// Poll every queued task exactly once and report what happened.
+    pub fn run_once(&mut self) -> RunTick<Ret> {
+        let mut completed = alloc::vec::Vec::new();
+        poll_fn(|cx| {
+            self.sweep(cx, None, |r| {
+                completed.push(r);
+                true
+            })
+        });
+        RunTick {
+            made_progress: !completed.is_empty(),
+            completed,
+            remaining: self.len(),
+        }
+    }
+
+    /// Borrow the pool as a task for another executor, resolving to every result once
+    /// the pool is empty.
+    ///
+    /// Same as awaiting `&mut pool`: each poll advances the pool with the outer
+    /// context; see [`poll_with`](LocalPool::poll_with) for how wake-ups reach it.
+    ///
+    /// ```rust
+    /// use minimal_executor::BusyLocalPool;
+    ///
+    /// let mut inner = BusyLocalPool::new(4);
+    /// inner.spawn(Box::pin(async { 1 })).unwrap();
+    /// let mut outer = BusyLocalPool::new(4);
+    /// outer.spawn(Box::pin(inner.as_task())).unwrap();
+    /// assert_eq!(outer.run(), vec![vec![1]]);
+    /// ```
+    pub fn as_task(&mut self) -> impl Future<Output = alloc::vec::Vec<Ret>> + use<'_, 'a, Ret, Q> {
+        self
+    }
+
+    /// Borrow the pool as a future that drives it until it is empty, discarding results.
+    ///
+    /// Like [`as_task`](LocalPool::as_task), but nothing is collected.
+    pub fn as_future(&mut self) -> impl Future<Output = ()> + use<'_, 'a, Ret, Q> {
+        futures::future::poll_fn(move |cx| self.poll_drain(cx, drop))
+    }
+
+    /// Polls with `cx` until the pool is empty or a pass completes nothing, handing
+    /// each result to `on_ready`. Shared by [`as_task`](LocalPool::as_task) and
+    /// [`as_future`](LocalPool::as_future).
+    fn poll_drain(&mut self, cx: &mut Context<'_>, mut on_ready: impl FnMut(Ret)) -> Poll<()> {
+        loop {
+            match self.poll_with(cx) {
+                Poll::Ready(Some(r)) => on_ready(r),
+                Poll::Ready(None) => return Poll::Ready(()),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    /// Poll queued tasks until one completes or each has been polled once.
+    ///
+    /// Like the other pools, this returns `Ready(None)` only when the pool is empty and
+    /// `Pending` when no task completed during the pass.
+    pub fn poll_once(&mut self) -> Poll<Option<Ret>> {
+        poll_fn(|cx| self.poll_with(cx))
+    }
+
+    /// Runs every task that can complete right now, discarding the results, and returns
+    /// `Ready(())` once the pool is empty.
+    ///
+    /// Never waits, so it can be called from an event loop that must not block, e.g. a
+    /// `requestAnimationFrame` or `queueMicrotask` callback in a browser; see
+    /// `spawn_on_microtask` (with the `wasm` feature) to reschedule it on wake-ups instead.
+    ///
+    /// ```rust
+    /// use core::task::Poll;
+    /// use minimal_executor::BusyLocalPool;
+    ///
+    /// let mut pool = BusyLocalPool::new(4);
+    /// pool.spawn(Box::pin(async {})).unwrap();
+    /// assert_eq!(pool.poll_step(), Poll::Ready(()));
+    /// ```
+    pub fn poll_step(&mut self) -> Poll<()> {
+        loop {
+            match self.poll_once() {
+                Poll::Ready(Some(_)) => {}
+                Poll::Ready(None) => return Poll::Ready(()),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    /// Like [`poll_once`](LocalPool::poll_once), but polls the tasks with `cx`, so the
+    /// wakers they register wake the caller.
+    ///
+    /// This lets the pool be driven from another executor or reactor: `Pending` means
+    /// `cx` will be woken once a task can make progress. With [`WakerStrategy::Busy`]
+    /// the tasks get `cx` itself; under the other strategies each task keeps its own
+    /// waker, which also wakes the context the task was last visited with.
+    pub fn poll_with(&mut self, cx: &mut Context<'_>) -> Poll<Option<Ret>> {
+        if self.is_empty() {
+            return Poll::Ready(None);
+        }
+        self.observe_depth();
+        let strategy = self.strategy;
+        for _ in 0..self.len() {
+            let (mut task, first) = match self.next_task() {
+                Some(next) => next,
+                None => break,
+            };
+            let step = self.poll_popped(&mut task, first, |task| task.poll(strategy, cx));
+            self.retire(&mut task, &step, first);
+            match step {
+                Step::Ready(ret) => return Poll::Ready(Some(ret)),
+                Step::Cancelled => {}
+                Step::Pending | Step::Skipped => self.requeue(task, first),
+            }
+        }
+        Poll::Pending
+    }
+
+    /// Accounts for a task that has been visited, releasing its id if it is done.
+    fn retire(&self, task: &mut Task<'a, Ret>, step: &Step<Ret>, first: bool) {
+        self.shared.record(step);
+        if let Step::Ready(_) | Step::Pending = step {
+            self.observe(task, matches!(step, Step::Ready(_)));
+        }
+        #[cfg(feature = "metrics")]
+        if let Step::Ready(_) | Step::Pending = step {
+            self.update_metrics(|m| {
+                m.polls += 1;
+                m.completed += matches!(step, Step::Ready(_)) as usize;
+            });
+        }
+        #[cfg(feature = "trace-events")]
+        if let (Some(id), Step::Ready(_) | Step::Pending) = (task.id, step) {
+            let ready = matches!(step, Step::Ready(_));
+            let mut events = self.events.borrow_mut();
+            events.push(Event::PollStart(id));
+            events.push(Event::PollEnd(id, ready));
+            if ready {
+                events.push(Event::Completed(id));
+            }
+        }
+        if let Step::Ready(_) | Step::Cancelled = step {
+            self.shared.release(first);
+            self.untrack(task);
+        }
+    }
+
+    /// Reports a poll to the observer, giving the task an id first if it has none.
+    fn observe(&self, task: &mut Task<'a, Ret>, ready: bool) {
+        let mut observer = self.observer.borrow_mut();
+        if observer.is_set() {
+            let id = *task.id.get_or_insert_with(|| self.ids.borrow_mut().insert());
+            observer.notify(id, if ready { PollState::Ready } else { PollState::Pending });
+        }
+    }
+
+    /// Forgets the id and allocation of a task that is leaving the pool.
+    fn untrack(&self, task: &Task<'a, Ret>) {
+        if let Some(id) = task.id {
+            self.ids.borrow_mut().remove(id);
+        }
+        #[cfg(debug_assertions)]
+        if let Some(addr) = task.addr {
+            self.live.borrow_mut().remove(&addr);
+        }
+    }
+
+    /// Registers a task taken from another pool under a fresh id and allocation entry.
+    fn adopt(&self, mut task: Task<'a, Ret>) -> Task<'a, Ret> {
+        #[cfg(debug_assertions)]
+        if let Some(addr) = task.addr {
+            self.live.borrow_mut().insert(addr);
+        }
+        #[cfg(feature = "trace-events")]
+        {
+            task = self.traced(task);
+        }
+        #[cfg(not(feature = "trace-events"))]
+        if task.id.is_some() {
+            task.id = Some(self.ids.borrow_mut().insert());
+        }
+        task
+    }
+
+    /// Claims a slot for `f` in the priority queue if `first`, otherwise in the normal
+    /// one, handing `f` back if the queue is full.
+    fn reserve<F>(&self, f: F, first: bool) -> Result<F, TrySpawnError<F>> {
+        match self.shared.reserve(first) {
+            true => Ok(f),
+            false => Err(TrySpawnError::new(SpawnErrorKind::Full, f)),
+        }
+    }
+
+    /// Applies the middleware to `f`. In debug builds, also asserts that the future's
+    /// allocation is not already in the pool, which means it was spawned twice.
+    fn new_task<F>(&mut self, f: F) -> Task<'a, Ret>
+        where F: UnsafeFutureObj<'a, Ret> {
+        #[cfg(debug_assertions)]
+        let task = {
+            let ptr = f.into_raw();
+            // zero-sized futures all share one dangling address
+            // SAFETY: `into_raw` returns a pointer that is valid until it is dropped
+            let addr = (unsafe { core::mem::size_of_val(&*ptr) } != 0).then_some(ptr as *const () as usize);
+            if let Some(addr) = addr {
+                // leaks the future on failure; dropping it could free an aliased allocation
+                debug_assert!(self.live.borrow_mut().insert(addr), "future spawned twice: {:#x}", addr);
+            }
+            let f = Traced::<'a, Ret, F> { ptr, _future: core::marker::PhantomData };
+            let mut task = Task::wrapped(self.middleware.get_mut().apply(LocalFutureObj::new(f)));
+            task.addr = addr;
+            task
+        };
+        #[cfg(not(debug_assertions))]
+        let task = Task::wrapped(self.middleware.get_mut().apply(LocalFutureObj::new(f)));
+        #[cfg(feature = "trace-events")]
+        let task = self.traced(task);
+        task
+    }
+
+    /// Gives `task` an id and records its spawn.
+    #[cfg(feature = "trace-events")]
+    fn traced(&self, mut task: Task<'a, Ret>) -> Task<'a, Ret> {
+        let id = self.ids.borrow_mut().insert();
+        task.id = Some(id);
+        self.events.borrow_mut().push(Event::Spawned(id));
+        task
+    }
+
+    /// Drops every queued task.
+    fn clear(&mut self) {
+        while let Some(task) = self.shared.first.pop().or_else(|| self.shared.queue.pop()) {
+            self.untrack(&task);
+        }
+        self.shared.reset_slots();
+        self.end_round();
+    }
+
+    /// Starts a new [`step`](LocalPool::step) round on the next call, once the queues
+    /// were consumed other than through `next_task`.
+    fn end_round(&self) {
+        self.round.set((0, 0));
+        self.visited.set(0);
+    }
+
+    /// Runs `poll` on a task popped from the `first` queue or the normal one. If it
+    /// panics, the task's slot and id are released as the panic unwinds, so the pool
+    /// stays usable after the panic is caught.
+    fn poll_popped<T>(&self, task: &mut Task<'a, Ret>, first: bool, poll: impl FnOnce(&mut Task<'a, Ret>) -> T) -> T {
+        let guard = Popped {
+            pool: self,
+            first,
+            id: task.id,
+            #[cfg(debug_assertions)]
+            addr: task.addr,
+        };
+        let ret = poll(task);
+        core::mem::forget(guard);
+        ret
+    }
+
+
+    fn queue(&self, first: bool) -> &Q {
+        self.shared.tier(first).0
+    }
+
+    /// Pops the next task of the priority queue if `first`, otherwise of the normal one,
+    /// applying the middleware to tasks pushed by spawners.
+    fn pop(&self, first: bool) -> Option<Task<'a, Ret>> {
+        let mut task = self.queue(first).pop()?;
+        if !task.wrapped {
+            task.future = self.middleware.borrow_mut().apply(task.future);
+            task.wrapped = true;
+        }
+        Some(task)
+    }
+
+    /// Puts a popped task back.
+    fn requeue(&self, task: Task<'a, Ret>, first: bool) {
+        #[cfg(feature = "metrics")]
+        self.update_metrics(|m| m.requeued += 1);
+        self.shared.requeue(task, first);
+    }
+
+    /// Records the queue depth at the start of a poll.
+    fn observe_depth(&self) {
+        #[cfg(feature = "metrics")]
+        self.update_metrics(|m| m.max_depth = m.max_depth.max(self.len()));
+    }
+
+    #[cfg(feature = "metrics")]
+    fn update_metrics(&self, f: impl FnOnce(&mut PoolMetrics)) {
+        let mut metrics = self.metrics.get();
+        f(&mut metrics);
+        self.metrics.set(metrics);
+    }
+
+    /// Picks the task for `poll_once`, visiting `spawn_priority` tasks at the start of
+    /// each round so they cannot starve the others.
+    fn next_task(&mut self) -> Option<(Task<'a, Ret>, bool)> {
+        let (mut first, mut normal) = self.round.get();
+        if (first, normal) == (0, 0) {
+            (first, normal) = (self.shared.first.len(), self.shared.queue.len());
+            self.visited.set(0);
+        }
+        self.visited.set(self.visited.get() + 1);
+        if first > 0 {
+            self.round.set((first - 1, normal));
+            if let Some(task) = self.pop(true) {
+                return Some((task, true));
+            }
+        }
+        self.round.set((self.round.get().0, normal.saturating_sub(1)));
+        match self.pop(false) {
+            Some(task) => Some((task, false)),
+            None => self.pop(true).map(|task| (task, true)),
+        }
+    }
+
+    fn poll_sweep(&mut self, budget: Option<&PollBudget>) -> Poll<Option<Ret>> {
+        if self.is_empty() {
             return Poll::Ready(None);
         }
         poll_fn(|cx| {
-            for _ in 0..len {
-                if let Some(mut future) = self.pool.pop() {
-                    match future.poll_unpin(cx) {
-                        Poll::Pending => {
-                            self.pool.push(future).expect("Queue full");
-                        }
-                        Poll::Ready(ret) => {
-                            return Poll::Ready(Some(ret));
-                        }
-                    }
-                }
+            let mut ret = None;
+            self.sweep(cx, budget, |r| {
+                ret = Some(r);
+                false
+            });
+            match ret {
+                Some(ret) => Poll::Ready(Some(ret)),
+                None => Poll::Pending,
             }
-            Poll::Pending
         })
     }
-    pub fn poll_once(&mut self) -> Poll<Option<Ret>> {
-        if let Some(mut future) = self.pool.pop() {
-            match poll_fn(|cx| future.poll_unpin(cx)) {
-                Poll::Pending => {
-                    self.pool.push(future).expect("Queue full");
-                    Poll::Pending
+
+    /// Polls every queued task once, priority tasks before the rest unless the
+    /// [fairness](LocalPool::with_fairness) ratio says the normal tasks are due.
+    ///
+    /// Each queue is visited for as many tasks as it held when its turn came. Pending
+    /// tasks are put back at the tail, behind the ones not visited yet, so a task that
+    /// keeps waking itself is still polled only once per sweep.
+    ///
+    /// Each completed result is passed to `on_ready`, which returns whether to keep
+    /// sweeping. Returns how many tasks were actually polled.
+    fn sweep(&self, cx: &mut Context<'_>, budget: Option<&PollBudget>, on_ready: impl FnMut(Ret) -> bool) -> usize {
+        self.observe_depth();
+        self.end_round();
+        let due = self.fairness.is_some_and(|ratio| self.starved.get() + 1 >= ratio);
+        let order = if due { [false, true] } else { [true, false] };
+        let mut reached = self.shared.queue.len() == 0;
+        let polled = self.sweep_tiers(cx, budget, order, &mut reached, on_ready);
+        self.starved.set(if reached { 0 } else { self.starved.get() + 1 });
+        polled
+    }
+
+    /// Does the work of [`sweep`](LocalPool::sweep), visiting the priority queue if
+    /// `true` and the normal one if `false` in `order`, and setting `reached` once a
+    /// normal task was visited.
+    fn sweep_tiers(&self, cx: &mut Context<'_>, budget: Option<&PollBudget>, order: [bool; 2],
+                   reached: &mut bool, mut on_ready: impl FnMut(Ret) -> bool) -> usize {
+        let mut polled = 0;
+        for first in order {
+            for _ in 0..self.queue(first).len() {
+                if budget.is_some_and(PollBudget::is_exhausted) {
+                    return polled;
                 }
-                Poll::Ready(ret) => {
-                    Poll::Ready(Some(ret))
+                let mut task = match self.pop(first) {
+                    Some(task) => task,
+                    None => break,
+                };
+                *reached |= !first;
+                let step = self.poll_popped(&mut task, first, |task| task.poll(self.strategy, &mut *cx));
+                self.retire(&mut task, &step, first);
+                if let Step::Ready(_) | Step::Pending = step {
+                    polled += 1;
+                    if let Some(budget) = budget {
+                        budget.consume();
+                    }
+                }
+                match step {
+                    Step::Ready(r) => {
+                        if !on_ready(r) {
+                            return polled;
+                        }
+                    }
+                    Step::Cancelled => {}
+                    Step::Pending | Step::Skipped => self.requeue(task, first),
                 }
             }
-        } else {
-            Poll::Ready(None)
         }
+        polled
     }
 }
 
+impl<'a, Q: TaskQueue<Task<'a, ()>>> LocalPool<'a, (), Q> {
+    /// Spawn `child` as a single task of this pool.
+    ///
+    /// Every time the parent polls the task, the child runs until a pass over its tasks
+    /// completes none, as [`as_future`](LocalPool::as_future) does; the task completes
+    /// once the child is empty. The child's results are discarded.
+    ///
+    /// If the queue is full, the error hands `child` back, boxed.
+    pub fn spawn_pool<R: 'a>(&mut self, child: LocalPool<'a, R>) -> Result<(), TrySpawnError<Box<LocalPool<'a, R>>>> {
+        let mut child = self.reserve(Box::new(child), false)?;
+        let task = self.new_task(Box::pin(async move { child.as_future().await }));
+        self.shared.enqueue(task, false);
+        Ok(())
+    }
+
+    /// Spawn `f` so that it can be cancelled through the returned [`AbortHandle`].
+    ///
+    /// Once aborted, the task is dropped the next time the pool polls it, without
+    /// polling `f` again, and the [`JoinHandle`] resolves to `Err(Canceled)`. Aborting
+    /// a task that has already completed does nothing.
+    pub fn spawn_abortable<F>(&mut self, f: F) -> Result<(AbortHandle, JoinHandle<F::Output>), TrySpawnError<F>>
+        where F: Future + 'a {
+        let f = self.reserve(f, false)?;
+        let (task, abort, handle) = with_abort_handle(f);
+        let task = self.new_task(Box::pin(task));
+        self.shared.enqueue(task, false);
+        Ok((abort, handle))
+    }
+
+    /// Spawn `f` for its side effects, discarding its output.
+    pub fn spawn_detached<F>(&mut self, f: F) -> Result<(), TrySpawnError<F>>
+        where F: Future + 'a {
+        let f = self.reserve(f, false)?;
+        let task = self.new_task(Box::pin(f.map(drop)));
+        self.shared.enqueue(task, false);
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::task::{Context, Poll};
+use crossbeam::queue::SegQueue;
+use futures::future::{FutureObj, LocalFutureObj};
+use futures::task::{Spawn, SpawnError, UnsafeFutureObj};
+use futures::FutureExt;
+use crate::poll_fn;
+
+/// A single-threaded pool that polls the most recently spawned task first.
+///
+/// Tasks sit on a stack: every pass starts at the top, and a pass ends early once a
+/// task has spawned new ones, so those are driven on the very next pass, ahead of
+/// everything spawned before them. This runs trees of tasks depth-first, which keeps
+/// their working set small, where the FIFO pools would run them breadth-first. Tasks
+/// are polled on every pass whether or not they were woken, as the busy pool does by
+/// default.
+///
+/// ```rust
+/// use minimal_executor::LifoLocalPool;
+///
+/// let mut pool = LifoLocalPool::new();
+/// for i in 0..3 {
+///     pool.spawn(Box::pin(async move { i }));
+/// }
+/// assert_eq!(pool.run(), vec![2, 1, 0]);
+/// ```
+#[derive(Debug)]
+pub struct LocalPool<'a, Ret = ()> {
+    /// The newest task is at the end.
+    stack: Vec<LocalFutureObj<'a, Ret>>,
+    /// Tasks sent by spawners, moved onto the stack at the start of every pass.
+    incoming: Arc<SegQueue<FutureObj<'static, Ret>>>,
+}
+
+/// A cloneable handle for spawning tasks onto a [`LocalPool`] from other threads.
+#[derive(Debug, Clone)]
+pub struct Spawner<Ret> {
+    tx: Weak<SegQueue<FutureObj<'static, Ret>>>,
+}
+
+impl<Ret> Spawner<Ret> {
+    pub fn spawn<F>(&self, f: F) -> Result<(), SpawnError>
+        where F: UnsafeFutureObj<'static, Ret> + Send {
+        self.send(FutureObj::new(f))
+    }
+
+    fn send(&self, future: FutureObj<'static, Ret>) -> Result<(), SpawnError> {
+        let tx = self.tx.upgrade().ok_or(SpawnError::shutdown())?;
+        tx.push(future);
+        Ok(())
+    }
+}
+
+impl Spawn for Spawner<()> {
+    fn spawn_obj(&self, future: FutureObj<'static, ()>) -> Result<(), SpawnError> {
+        self.send(future)
+    }
+}
+
+impl<'a, Ret> LocalPool<'a, Ret> {
+    /// Create a new, empty pool of tasks.
+    pub fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            incoming: Arc::new(SegQueue::new()),
+        }
+    }
+    pub fn spawner(&self) -> Spawner<Ret> {
+        Spawner {
+            tx: Arc::downgrade(&self.incoming),
+        }
+    }
+    /// Number of unfinished tasks, including the ones spawners queued.
+    pub fn len(&self) -> usize {
+        self.stack.len() + self.incoming.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn spawn<F>(&mut self, f: F)
+        where F: UnsafeFutureObj<'a, Ret> {
+        self.stack.push(LocalFutureObj::new(f));
+    }
+
+    /// Run all tasks in the pool to completion, newest first.
+    ///
+    /// The function will block the calling thread until *all* tasks in the pool
+    /// are complete, including any spawned while running existing tasks.
+    pub fn run(&mut self) -> Vec<Ret> {
+        let mut results = Vec::with_capacity(self.len());
+        loop {
+            match self.poll_once() {
+                Poll::Ready(Some(r)) => results.push(r),
+                Poll::Ready(None) => return results,
+                Poll::Pending => {}
+            }
+        }
+    }
+
+    /// Runs all tasks and returns after completing one future or until no more progress
+    /// can be made.
+    pub fn try_run_one(&mut self) -> Poll<Ret> {
+        match self.poll_once() {
+            Poll::Ready(Some(r)) => Poll::Ready(r),
+            Poll::Ready(None) | Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Poll tasks from the top of the stack down until one completes, one spawns new
+    /// tasks through a spawner, or each has been polled once.
+    ///
+    /// Like the other pools, this returns `Ready(None)` only when the pool is empty and
+    /// `Pending` when no task completed during the pass.
+    pub fn poll_once(&mut self) -> Poll<Option<Ret>> {
+        poll_fn(|cx| self.poll_with(cx))
+    }
+
+    /// Like [`poll_once`](LocalPool::poll_once), but polls the tasks with `cx`, so the
+    /// wakers they register wake the caller.
+    pub fn poll_with(&mut self, cx: &mut Context<'_>) -> Poll<Option<Ret>> {
+        while let Some(fut) = self.incoming.pop() {
+            self.stack.push(fut.into());
+        }
+        if self.stack.is_empty() {
+            return Poll::Ready(None);
+        }
+        for i in (0..self.stack.len()).rev() {
+            if let Poll::Ready(ret) = self.stack[i].poll_unpin(cx) {
+                self.stack.remove(i);
+                return Poll::Ready(Some(ret));
+            }
+            if !self.incoming.is_empty() {
+                // the task spawned children; start the next pass with them
+                break;
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl<'a, Ret> Default for LocalPool<'a, Ret> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
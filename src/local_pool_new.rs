@@ -1,14 +1,25 @@
+use futures::future::FusedFuture;
+use alloc::boxed::Box;
 use alloc::sync::{Arc, Weak};
 use futures::stream::FuturesUnordered;
 use futures::future::LocalFutureObj;
-use futures::StreamExt;
-use core::task::{Poll};
+use futures::{Stream, StreamExt};
+use core::task::{Context, Poll};
+use core::sync::atomic::{AtomicBool, Ordering};
 use crossbeam::queue::SegQueue;
 use futures::task::UnsafeFutureObj;
-use crate::poll_fn;
+use futures::future::AbortHandle;
+use crate::{drop_on_panic, poll_fn};
 use futures::future::FutureObj;
 use futures::task::Spawn;
 use futures::task::SpawnError;
+use crate::middleware::Middleware;
+use crate::limit::TaskLimit;
+use crate::spawn_error::{SpawnErrorKind, TrySpawnError};
+use crate::local_spawn::{LocalQueue, LocalSpawner};
+use crate::join::{with_abort_handle, with_handle, JoinHandle};
+use futures::FutureExt;
+use core::future::Future;
 
 /// A single-threaded task pool for polling futures to completion.
 ///
@@ -24,26 +35,115 @@ use futures::task::SpawnError;
 #[derive(Debug)]
 pub struct LocalPool<'a, Ret = ()> {
     pool: FuturesUnordered<LocalFutureObj<'a, Ret>>,
-    other: Arc<SegQueue<LocalFutureObj<'static, Ret>>>,
+    other: Arc<SegQueue<FutureObj<'static, Ret>>>,
+    /// Tasks queued by [`LocalSpawner`]s.
+    local: LocalQueue<'a, Ret>,
+    middleware: Middleware<'a, Ret>,
+    limit: Option<Arc<TaskLimit>>,
+    /// Caps the tasks spawners may queue before the pool takes them in, see
+    /// [`bounded`](LocalPool::bounded).
+    backlog: Option<Arc<TaskLimit>>,
+    /// Shared with the spawners; set once the pool stops accepting their tasks.
+    closed: Arc<AtomicBool>,
+    /// Results collected while the pool is awaited as a future.
+    awaited: alloc::vec::Vec<Ret>,
+    /// Tasks that ran to completion.
+    completed: usize,
 }
 
+/// A cloneable handle for spawning tasks onto a [`LocalPool`] from other threads.
+///
+/// Tasks are pushed onto a lock-free queue shared with the pool, which moves them into
+/// its own set at the start of every [`poll_once`](LocalPool::poll_once). Unlike the
+/// std-only `Spawner` of the channel-based pool, this works without the `std` feature.
+///
+/// The queue is an unbounded `SegQueue` rather than an `ArrayQueue`, so spawning only
+/// fails when the pool asks for it: with [`bounded`](LocalPool::bounded) or a
+/// [task limit](LocalPool::with_task_limit).
 #[derive(Clone)]
 pub struct Spawner<Ret> {
-    tx: Weak<SegQueue<LocalFutureObj<'static, Ret>>>,
+    tx: Weak<SegQueue<FutureObj<'static, Ret>>>,
+    limit: Option<Arc<TaskLimit>>,
+    backlog: Option<Arc<TaskLimit>>,
+    /// Set by [`shutdown`](LocalPool::shutdown).
+    closed: Arc<AtomicBool>,
 }
 
-impl Spawner<()> {
+impl<Ret> Spawner<Ret> {
     pub fn spawn<F>(&self, f: F) -> Result<(), SpawnError>
-        where F: UnsafeFutureObj<'static, ()> + Send {
-        self.spawn_obj(FutureObj::new(f))
+        where F: UnsafeFutureObj<'static, Ret> + Send {
+        self.send(FutureObj::new(f))
+    }
+
+    /// Like [`spawn`](Spawner::spawn), but hands `f` back if the pool turns it away.
+    ///
+    /// The pool is [`Full`](SpawnErrorKind::Full) while its
+    /// [task limit](LocalPool::with_task_limit) is reached, or while spawners have
+    /// queued as many tasks as a [`bounded`](LocalPool::bounded) pool allows.
+    pub fn try_spawn<F>(&self, f: F) -> Result<(), TrySpawnError<F>>
+        where F: UnsafeFutureObj<'static, Ret> + Send {
+        match self.admit() {
+            Ok(tx) => {
+                tx.push(FutureObj::new(f));
+                Ok(())
+            }
+            Err(kind) => Err(TrySpawnError::new(kind, f)),
+        }
+    }
+
+    /// Queues a task for the pool, unless the pool is gone or full.
+    fn send(&self, future: FutureObj<'static, Ret>) -> Result<(), SpawnError> {
+        let tx = self.admit().map_err(|_| SpawnError::shutdown())?;
+        tx.push(future);
+        Ok(())
+    }
+
+    /// Checks the pool still takes tasks and claims a slot under its limits.
+    fn admit(&self) -> Result<Arc<SegQueue<FutureObj<'static, Ret>>>, SpawnErrorKind> {
+        let tx = self.tx.upgrade().ok_or(SpawnErrorKind::Shutdown)?;
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpawnErrorKind::Shutdown);
+        }
+        if let Some(limit) = &self.limit {
+            if !limit.try_acquire() {
+                return Err(SpawnErrorKind::Full);
+            }
+        }
+        if let Some(backlog) = &self.backlog {
+            if !backlog.try_acquire() {
+                if let Some(limit) = &self.limit {
+                    limit.release(1);
+                }
+                return Err(SpawnErrorKind::Full);
+            }
+        }
+        Ok(tx)
+    }
+}
+
+impl Spawner<()> {
+    /// Spawn `f` and return a [`JoinHandle`] resolving to its output once the pool
+    /// has run it.
+    ///
+    /// ```rust
+    /// use minimal_executor::NewLocalPool;
+    ///
+    /// let mut pool = NewLocalPool::new();
+    /// let handle = pool.spawner().spawn_with_handle(async { 5 }).unwrap();
+    /// pool.run();
+    /// assert_eq!(handle.block(), Ok(5));
+    /// ```
+    pub fn spawn_with_handle<F>(&self, f: F) -> Result<JoinHandle<F::Output>, SpawnError>
+        where F: Future + Send + 'static, F::Output: Send {
+        let (task, handle) = with_handle(f);
+        self.spawn(Box::pin(task))?;
+        Ok(handle)
     }
 }
 
 impl Spawn for Spawner<()> {
     fn spawn_obj(&self, future: FutureObj<'static, ()>) -> Result<(), SpawnError> {
-        let tx = self.tx.upgrade().ok_or(SpawnError::shutdown())?;
-        tx.push(future.into());
-        Ok(())
+        self.send(future)
     }
 }
 
@@ -53,18 +153,127 @@ impl<'a, Ret> LocalPool<'a, Ret> {
     pub fn new() -> Self {
         Self {
             pool: FuturesUnordered::new(),
-            other: Arc::new(SegQueue::new())
+            other: Arc::new(SegQueue::new()),
+            local: LocalQueue::default(),
+            middleware: Middleware::new(),
+            limit: None,
+            backlog: None,
+            closed: Arc::new(AtomicBool::new(false)),
+            awaited: alloc::vec::Vec::new(),
+            completed: 0,
         }
     }
+
+    /// Create a pool whose spawners can queue at most `cap` tasks before it takes
+    /// them in.
+    ///
+    /// Spawners are refused while the backlog is full, which tells producers to slow
+    /// down when the pool is not run often enough, instead of queuing without bound.
+    /// Tasks the pool has taken in no longer count, and neither do ones spawned on the
+    /// pool itself.
+    ///
+    /// ```rust
+    /// use minimal_executor::{NewLocalPool, SpawnErrorKind};
+    ///
+    /// let mut pool = NewLocalPool::<u32>::bounded(1);
+    /// let spawner = pool.spawner();
+    /// spawner.try_spawn(Box::pin(async { 1 })).unwrap();
+    /// let err = spawner.try_spawn(Box::pin(async { 2 })).unwrap_err();
+    /// assert_eq!(err.kind, SpawnErrorKind::Full);
+    ///
+    /// assert_eq!(pool.run(), vec![1]);
+    /// spawner.try_spawn(err.future).unwrap();
+    /// assert_eq!(pool.run(), vec![2]);
+    /// ```
+    pub fn bounded(cap: usize) -> Self {
+        let mut pool = Self::new();
+        pool.backlog = Some(TaskLimit::new(cap));
+        pool
+    }
+
+    /// Install a spawn middleware layer, applied to every task spawned afterwards.
+    ///
+    /// The layer receives the task and returns the (possibly wrapped) future that is
+    /// actually stored, e.g. to add timing or tracing. Layers compose: each one wraps
+    /// the result of the layers installed before it.
+    pub fn with_middleware<F>(mut self, layer: F) -> Self
+        where F: FnMut(LocalFutureObj<'a, Ret>) -> LocalFutureObj<'a, Ret> + 'a {
+        self.middleware.push(layer);
+        self
+    }
+    /// Cap the pool at `max` tasks, counting both queued and running ones.
+    ///
+    /// Every [`Spawner`] and [`LocalSpawner`] of the pool shares the cap. Once it is
+    /// reached their `spawn` fails and [`try_spawn`](Spawner::try_spawn) reports
+    /// [`Full`](SpawnErrorKind::Full), until a task completes. Tasks spawned on the pool
+    /// itself are counted but never refused. Unlike [`bounded`](LocalPool::bounded), which
+    /// only limits the tasks waiting to be spawned, this caps the whole pool.
+    pub fn with_task_limit(mut self, max: usize) -> Self {
+        self.limit = Some(TaskLimit::new(max));
+        self
+    }
     pub fn spawner(&self) -> Spawner<Ret> {
         Spawner {
             tx: Arc::downgrade(&self.other),
+            limit: self.limit.clone(),
+            backlog: self.backlog.clone(),
+            closed: self.closed.clone(),
         }
     }
+    /// A handle for spawning `!Send` tasks onto the pool from its own thread.
+    pub fn local_spawner(&self) -> LocalSpawner<'a, Ret> {
+        LocalSpawner::new(&self.local, self.limit.clone(), self.closed.clone())
+    }
+    /// Number of unfinished tasks, including the ones spawners queued that the pool
+    /// has not taken in yet.
+    pub fn len(&self) -> usize {
+        self.pool.len() + self.pending_spawns()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Number of tasks queued by spawners that the pool has not taken in yet.
+    pub fn pending_spawns(&self) -> usize {
+        self.other.len() + self.local.borrow().len()
+    }
     pub fn spawn<F>(&mut self, f: F)
         where F: UnsafeFutureObj<'a, Ret> {
-        self.pool.push(LocalFutureObj::new(f))
+        if let Some(limit) = &self.limit {
+            limit.acquire();
+        }
+        let future = self.middleware.apply(LocalFutureObj::new(f));
+        self.pool.push(future)
     }
+    /// Spawn a fused future, skipping it if it has already terminated.
+    ///
+    /// A terminated future usually means a completed one was accidentally reused: debug
+    /// builds panic on it, release builds skip it and return `false`.
+    pub fn spawn_checked<F>(&mut self, f: F) -> bool
+        where F: FusedFuture<Output = Ret> + 'a {
+        debug_assert!(!f.is_terminated(), "spawned a future that already terminated");
+        if f.is_terminated() {
+            return false;
+        }
+        self.spawn(Box::pin(f));
+        true
+    }
+    /// Like [`run`](LocalPool::run), but returns a [`StuckError`](crate::StuckError)
+    /// once `max_idle_rounds` passes in a row completed no task, instead of spinning
+    /// forever on tasks that never complete.
+    ///
+    /// See [`PoolExt::run_bounded`](crate::PoolExt::run_bounded).
+    pub fn run_bounded(&mut self, max_idle_rounds: usize) -> Result<alloc::vec::Vec<Ret>, crate::StuckError> {
+        crate::PoolExt::run_bounded(self, max_idle_rounds)
+    }
+
+    /// Like [`run`](LocalPool::run), but hands out the results one at a time as tasks
+    /// complete, so the caller can stop early and leave the rest in the pool.
+    ///
+    /// See [`RunIter`](crate::RunIter).
+    pub fn run_iter(&mut self) -> crate::RunIter<'_, Self> {
+        crate::RunIter::new(self)
+    }
+
     /// Run all tasks in the pool to completion.
     ///
     /// ```rust
@@ -80,20 +289,155 @@ impl<'a, Ret> LocalPool<'a, Ret> {
     /// ```
     ///
     /// The function will block the calling thread until *all* tasks in the pool
-    /// are complete, including any spawned while running existing tasks.
+    /// are complete, including any spawned while running existing tasks. With the `std`
+    /// feature, a panicking task drops every task left in the pool as the panic unwinds
+    /// out of this call.
     pub fn run(&mut self) -> alloc::vec::Vec<Ret> {
-        let mut results = alloc::vec::Vec::new();
-        loop {
-            let ret = self.poll_once();
+        self.run_fold(alloc::vec::Vec::new(), |mut results, r| {
+            results.push(r);
+            results
+        })
+    }
 
-            // no queued tasks; we may be done
-            match ret {
-                Poll::Pending => {}
-                Poll::Ready(None) => break,
-                Poll::Ready(Some(r)) => { results.push(r); }
+    /// Stop accepting tasks from spawners, then run every task left to completion and
+    /// return the results.
+    ///
+    /// From then on, spawners get a shutdown [`SpawnError`], including the ones held by
+    /// tasks still running, so work spawned during the wind-down is rejected rather than
+    /// run. A spawn racing this call may still be accepted and is run. Tasks spawned
+    /// directly on the pool are not affected.
+    ///
+    /// ```rust
+    /// use minimal_executor::NewLocalPool;
+    ///
+    /// let mut pool = NewLocalPool::new();
+    /// let spawner = pool.spawner();
+    /// spawner.spawn(Box::pin(async { 1 })).unwrap();
+    /// assert_eq!(pool.shutdown(), vec![1]);
+    /// assert!(spawner.spawn(Box::pin(async { 2 })).is_err());
+    /// ```
+    pub fn shutdown(&mut self) -> alloc::vec::Vec<Ret> {
+        self.closed.store(true, Ordering::Release);
+        self.run()
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Run all tasks to completion, folding each result into an accumulator as it
+    /// completes instead of collecting them.
+    ///
+    /// ```rust
+    /// use minimal_executor::NewLocalPool;
+    ///
+    /// let mut pool = NewLocalPool::new();
+    /// for i in 1..=3 {
+    ///     pool.spawn(Box::pin(async move { i }));
+    /// }
+    /// assert_eq!(pool.run_fold(0, |sum, i| sum + i), 6);
+    /// ```
+    pub fn run_fold<B, F: FnMut(B, Ret) -> B>(&mut self, init: B, mut f: F) -> B {
+        drop_on_panic(self, |pool| {
+            let mut acc = init;
+            loop {
+                let ret = pool.poll_once();
+
+                // no queued tasks; we may be done
+                match ret {
+                    Poll::Pending => {}
+                    Poll::Ready(None) => break,
+                    Poll::Ready(Some(r)) => { acc = f(acc, r); }
+                }
             }
+            acc
+        }, Self::clear)
+    }
+
+    /// Run all tasks to completion, handing each result to `on_result` as it completes.
+    ///
+    /// Nothing is collected, so this does not allocate for the results.
+    ///
+    /// ```rust
+    /// use minimal_executor::NewLocalPool;
+    ///
+    /// let mut pool = NewLocalPool::new();
+    /// for i in 1..=3 {
+    ///     pool.spawn(Box::pin(async move { i }));
+    /// }
+    /// let mut max = 0;
+    /// pool.run_with(|i| max = max.max(i));
+    /// assert_eq!(max, 3);
+    /// ```
+    pub fn run_with<F: FnMut(Ret)>(&mut self, mut on_result: F) {
+        self.run_fold((), |(), r| on_result(r))
+    }
+
+    /// Borrow the pool as a task for another executor, resolving to every result once
+    /// the pool is empty.
+    ///
+    /// Same as awaiting `&mut pool`: each poll advances the pool with the outer
+    /// context, so wake-ups inside the pool wake the task holding it.
+    ///
+    /// ```rust
+    /// use minimal_executor::NewLocalPool;
+    ///
+    /// let mut inner = NewLocalPool::new();
+    /// inner.spawn(Box::pin(async { 1 }));
+    /// let mut outer = NewLocalPool::new();
+    /// outer.spawn(Box::pin(inner.as_task()));
+    /// assert_eq!(outer.run(), vec![vec![1]]);
+    /// ```
+    pub fn as_task(&mut self) -> impl Future<Output = alloc::vec::Vec<Ret>> + use<'_, 'a, Ret> {
+        self
+    }
+
+    /// Stream the results of tasks as they complete, ending once the pool is empty.
+    ///
+    /// Every poll of the stream polls the pool once, picking up tasks spawned in the
+    /// meantime. While no task completes the stream wakes itself, so it keeps the pool
+    /// running when driven by another executor. Unconsumed tasks stay in the pool.
+    ///
+    /// ```rust
+    /// use futures::StreamExt;
+    /// use minimal_executor::NewLocalPool;
+    ///
+    /// let mut pool = NewLocalPool::new();
+    /// pool.spawn(Box::pin(async { 2 }));
+    /// let sum = minimal_executor::block_on(pool.stream().fold(0, |sum, i| async move { sum + i }));
+    /// assert_eq!(sum, 2);
+    /// ```
+    pub fn stream(&mut self) -> impl Stream<Item = Ret> + use<'_, 'a, Ret> {
+        futures::stream::poll_fn(move |cx| match self.poll_once() {
+            Poll::Ready(ret) => Poll::Ready(ret),
+            Poll::Pending => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+    }
+
+    /// Drive the pool's tasks until `f` completes, and return its output.
+    ///
+    /// `f` is polled once per pass over the tasks, before the tasks themselves. Tasks
+    /// still pending when `f` completes stay in the pool; the results of tasks that
+    /// completed in the meantime are dropped.
+    ///
+    /// ```rust
+    /// use minimal_executor::NewLocalPool;
+    ///
+    /// let mut pool = NewLocalPool::new();
+    /// pool.spawn(Box::pin(async {}));
+    /// assert_eq!(pool.run_until(async { 3 }), 3);
+    /// ```
+    pub fn run_until<F: Future>(&mut self, f: F) -> F::Output {
+        futures::pin_mut!(f);
+        loop {
+            if let Poll::Ready(t) = poll_fn(|cx| f.as_mut().poll(cx)) {
+                return t;
+            }
+            let _ = self.poll_once();
         }
-        results
     }
 
     /// Runs all tasks and returns after completing one future or until no more progress
@@ -103,9 +447,9 @@ impl<'a, Ret> LocalPool<'a, Ret> {
     ///
     /// use futures::task::LocalSpawnExt;
     /// use futures::future::{ready, pending};
-    /// use minimal_executor::LocalPool;
+    /// use minimal_executor::NewLocalPool;
     ///
-    /// let mut pool: LocalPool<'_, ()> = LocalPool::new();
+    /// let mut pool: NewLocalPool<'_, ()> = NewLocalPool::new();
     /// pool.spawn(Box::pin(ready(())));
     /// pool.spawn(Box::pin(ready(())));
     /// pool.spawn(Box::pin(pending()));
@@ -122,7 +466,9 @@ impl<'a, Ret> LocalPool<'a, Ret> {
     /// that there are no tasks left for which progress can be made or after exactly one
     /// task was completed; Remaining incomplete tasks in the pool can continue with
     /// further use of one of the pool's run or poll methods.
-    /// Though only one task will be completed, progress may be made on multiple tasks.
+    /// Though only one task will be completed, progress may be made on multiple tasks:
+    /// the underlying `FuturesUnordered` polls woken tasks until the first one completes
+    /// and returns right away, leaving the rest for the next call.
     pub fn try_run_one(&mut self) -> Poll<Ret> {
         let ret = self.poll_once();
         match ret {
@@ -138,14 +484,213 @@ impl<'a, Ret> LocalPool<'a, Ret> {
         }
     }
 
+    /// Run tasks until `max` of them have completed or none of the rest can make
+    /// progress, returning the results in completion order.
+    ///
+    /// Sits between [`try_run_one`](LocalPool::try_run_one) and [`run`](LocalPool::run):
+    /// it stops at the first [`try_run_one`](LocalPool::try_run_one) that completes
+    /// nothing, so pending tasks make it return early instead of spinning. Unfinished
+    /// tasks stay in the pool.
+    pub fn run_n(&mut self, max: usize) -> alloc::vec::Vec<Ret> {
+        let mut results = alloc::vec::Vec::new();
+        while results.len() < max {
+            match self.try_run_one() {
+                Poll::Ready(r) => results.push(r),
+                Poll::Pending => break,
+            }
+        }
+        results
+    }
+
+
+    /// Take every unfinished task out of the pool, e.g. to re-spawn them on another
+    /// executor or count what was abandoned at shutdown.
+    ///
+    /// This is best-effort: a task is only handed back if `FuturesUnordered` has not
+    /// consumed it, so tasks whose results were already returned by a poll are gone.
+    /// Tasks spawned on the pool come back with the middleware applied; ones still
+    /// queued by spawners come back as they were spawned.
+    ///
+    /// ```rust
+    /// use minimal_executor::NewLocalPool;
+    ///
+    /// let mut pool: NewLocalPool<'_, ()> = NewLocalPool::new();
+    /// pool.spawn(Box::pin(futures::future::pending()));
+    /// pool.spawner().spawn(Box::pin(futures::future::pending())).unwrap();
+    /// assert_eq!(pool.drain().len(), 2);
+    /// assert!(pool.run().is_empty());
+    /// ```
+    pub fn drain(&mut self) -> alloc::vec::Vec<LocalFutureObj<'a, Ret>> {
+        let mut drained: alloc::vec::Vec<_> = core::mem::take(&mut self.pool).into_iter().collect();
+        while let Some(fut) = self.pop_spawned() {
+            drained.push(fut.into());
+        }
+        drained.append(&mut self.local.borrow_mut());
+        if let Some(limit) = &self.limit {
+            limit.release(drained.len());
+        }
+        drained
+    }
+
+    /// Takes in the next task a spawner queued, freeing its place in the backlog.
+    fn pop_spawned(&self) -> Option<FutureObj<'static, Ret>> {
+        let fut = self.other.pop()?;
+        if let Some(backlog) = &self.backlog {
+            backlog.release(1);
+        }
+        Some(fut)
+    }
+
+    /// Drops every task, including the ones still queued by spawners.
+    fn clear(&mut self) {
+        let mut dropped = self.pool.len();
+        self.pool.clear();
+        while self.pop_spawned().is_some() {
+            dropped += 1;
+        }
+        dropped += core::mem::take(&mut *self.local.borrow_mut()).len();
+        if let Some(limit) = &self.limit {
+            limit.release(dropped);
+        }
+    }
 
     pub fn poll_once(&mut self) -> Poll<Option<Ret>> {
-        poll_fn(|cx| {
-            while let Some(fut) = self.other.pop() {
-                self.pool.push(fut);
+        poll_fn(|cx| self.poll_with(cx))
+    }
+
+    /// Runs every task that can complete right now, discarding the results, and returns
+    /// `Ready(())` once the pool is empty.
+    ///
+    /// Never waits, so it can be called from an event loop that must not block, e.g. a
+    /// `requestAnimationFrame` or `queueMicrotask` callback in a browser; see
+    /// `spawn_on_microtask` (with the `wasm` feature) to reschedule it on wake-ups instead.
+    ///
+    /// ```rust
+    /// use core::task::Poll;
+    /// use minimal_executor::NewLocalPool;
+    ///
+    /// let mut pool = NewLocalPool::new();
+    /// pool.spawn(Box::pin(async {}));
+    /// assert_eq!(pool.poll_step(), Poll::Ready(()));
+    /// ```
+    pub fn poll_step(&mut self) -> Poll<()> {
+        loop {
+            match self.poll_once() {
+                Poll::Ready(Some(_)) => {}
+                Poll::Ready(None) => return Poll::Ready(()),
+                Poll::Pending => return Poll::Pending,
             }
-            self.pool.poll_next_unpin(cx)
-        })
+        }
+    }
+
+    /// Completes every task that is ready right now and collects the results, along
+    /// with whether the pool is now empty.
+    ///
+    /// Same as calling [`poll_once`](LocalPool::poll_once) until it stops returning
+    /// results, without the round-trip per result.
+    ///
+    /// ```rust
+    /// use minimal_executor::NewLocalPool;
+    ///
+    /// let mut pool = NewLocalPool::new();
+    /// pool.spawn(Box::pin(async { 1 }));
+    /// pool.spawn(Box::pin(async { 2 }));
+    /// let (mut results, empty) = pool.poll_all_ready();
+    /// results.sort();
+    /// assert_eq!((results, empty), (vec![1, 2], true));
+    /// ```
+    pub fn poll_all_ready(&mut self) -> (alloc::vec::Vec<Ret>, bool) {
+        let mut results = alloc::vec::Vec::new();
+        loop {
+            match self.poll_once() {
+                Poll::Ready(Some(r)) => results.push(r),
+                Poll::Ready(None) => return (results, true),
+                Poll::Pending => return (results, false),
+            }
+        }
+    }
+
+    /// Like [`poll_once`](LocalPool::poll_once), but polls the tasks with `cx`, so the
+    /// wakers they register wake the caller.
+    ///
+    /// This lets the pool be driven from another executor or reactor; `Pending` means
+    /// `cx` will be woken once a task can make progress.
+    pub fn poll_with(&mut self, cx: &mut Context<'_>) -> Poll<Option<Ret>> {
+        while let Some(fut) = self.pop_spawned() {
+            let fut = self.middleware.apply(fut.into());
+            self.pool.push(fut);
+        }
+        let local = core::mem::take(&mut *self.local.borrow_mut());
+        for fut in local {
+            let fut = self.middleware.apply(fut);
+            self.pool.push(fut);
+        }
+        let ret = self.pool.poll_next_unpin(cx);
+        if let Poll::Ready(Some(_)) = ret {
+            self.completed += 1;
+            if let Some(limit) = &self.limit {
+                limit.release(1);
+            }
+        }
+        ret
+    }
+}
+
+impl<'a> LocalPool<'a, ()> {
+    /// Spawn `fut` and hand its output to `sink` when it completes.
+    ///
+    /// This lets individual tasks route their results to different destinations
+    /// instead of having them collected by `run`.
+    pub fn spawn_to_sink<Fut, S>(&mut self, fut: Fut, sink: S)
+        where Fut: Future + 'a, S: FnOnce(Fut::Output) + 'a {
+        self.spawn(Box::pin(fut.map(sink)));
+    }
+
+    /// Spawn `f` so that it can be cancelled through the returned [`AbortHandle`].
+    ///
+    /// Once aborted, the task is dropped the next time the pool polls it, without
+    /// polling `f` again, and the [`JoinHandle`] resolves to `Err(Canceled)`. Aborting
+    /// a task that has already completed does nothing.
+    pub fn spawn_abortable<F>(&mut self, f: F) -> (AbortHandle, JoinHandle<F::Output>)
+        where F: Future + 'a {
+        let (task, abort, handle) = with_abort_handle(f);
+        self.spawn(Box::pin(task));
+        (abort, handle)
+    }
+
+    /// Spawn `f` for its side effects, discarding its output.
+    pub fn spawn_detached<F>(&mut self, f: F)
+        where F: Future + 'a {
+        self.spawn(Box::pin(f.map(drop)));
+    }
+}
+
+/// Awaiting the pool runs it to completion with the caller's context, resolving to
+/// every result once the pool is empty.
+///
+/// Tasks are polled with the caller's waker, so the outer executor is woken whenever a
+/// task is. Tasks pushed by a [`Spawner`] while the pool is waiting do not wake it; they
+/// are picked up the next time the pool is polled.
+impl<'a, Ret> Future for &mut LocalPool<'a, Ret> {
+    type Output = alloc::vec::Vec<Ret>;
+
+    fn poll(self: core::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let pool = &mut **self.get_mut();
+        loop {
+            match pool.poll_with(cx) {
+                Poll::Ready(Some(r)) => pool.awaited.push(r),
+                Poll::Ready(None) => return Poll::Ready(core::mem::take(&mut pool.awaited)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// A one-line summary for logs, e.g. `pool: 3 tasks, 1024 completed`, with the
+/// [task limit](LocalPool::with_task_limit) as capacity if there is one.
+impl<Ret> core::fmt::Display for LocalPool<'_, Ret> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        crate::write_summary(f, self.len(), self.limit.as_ref().map(|l| l.max()), self.completed, None)
     }
 }
 
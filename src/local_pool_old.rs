@@ -1,12 +1,34 @@
+use futures::future::FusedFuture;
 use futures::stream::FuturesUnordered;
 use futures::future::LocalFutureObj;
-use futures::StreamExt;
-use core::task::{Poll};
+use futures::{Sink, Stream, StreamExt};
+use core::pin::Pin;
+use futures::stream::{LocalBoxStream, SelectAll};
+use core::task::{Context, Poll};
+use core::sync::atomic::{AtomicBool, Ordering};
 use futures::task::UnsafeFutureObj;
-use crate::poll_fn;
+use futures::future::AbortHandle;
+use crate::join::{with_abort_handle, JoinHandle};
+use crate::{drop_on_panic, poll_fn};
 use futures::future::FutureObj;
 use futures::task::Spawn;
 use futures::task::SpawnError;
+use futures::future::{FutureExt, Shared};
+use core::future::Future;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::hash::Hash;
+use std::collections::HashMap;
+use crate::waker::{TaskWake, WakerStrategy};
+use crate::middleware::Middleware;
+use crate::limit::TaskLimit;
+use crate::slab::{Slab, TaskId};
+use crate::local_spawn::{LocalQueue, LocalSpawner};
+use crate::spawn_error::{SpawnErrorKind, TrySpawnError};
+use alloc::sync::Arc;
+use crate::deadline::{with_handle, DeadlineHandle};
+use crate::tracked::{pool_key, track_current, track_root, CompletionHandle};
+use std::time::{Duration, Instant};
 
 /// A single-threaded task pool for polling futures to completion.
 ///
@@ -19,29 +41,231 @@ use futures::task::SpawnError;
 /// [`spawner()`](LocalPool::spawner) method. Because the executor is
 /// single-threaded, it supports a special form of task spawning for non-`Send`
 /// futures, via [`spawn_local_obj`](futures_task::LocalSpawn::spawn_local_obj).
+///
+/// If a task panics inside one of the `run` methods, every task left in the pool is
+/// dropped as the panic unwinds out of the call. [`run_iter`](LocalPool::run_iter),
+/// [`results`](LocalPool::results) and single steps such as
+/// [`poll_once`](LocalPool::poll_once) leave the remaining tasks in place.
 #[derive(Debug)]
 pub struct LocalPool<'a, Ret = ()> {
     pool: FuturesUnordered<LocalFutureObj<'a, Ret>>,
     rx: kanal::Receiver<FutureObj<'static, Ret>>,
     tx: kanal::Sender<FutureObj<'static, Ret>>,
+    /// Tasks queued by [`LocalSpawner`]s.
+    local: LocalQueue<'a, Ret>,
+    on_empty: Option<OnEmpty<'a>>,
+    middleware: Middleware<'a, Ret>,
+    streams: StreamTasks<'a, Ret>,
+    deadlines: DeadlineTasks<'a, Ret>,
+    limit: Option<Arc<TaskLimit>>,
+    /// Shared with the spawners; set once the pool stops accepting their tasks.
+    closed: Arc<AtomicBool>,
+    /// Results collected while the pool is awaited as a future.
+    awaited: Vec<Ret>,
+    /// Results produced so far, by tasks and stream tasks.
+    completed: usize,
+}
+
+struct OnEmpty<'a>(Box<dyn FnOnce() + 'a>);
+
+/// Tasks spawned with [`spawn_stream_task`](LocalPool::spawn_stream_task).
+struct StreamTasks<'a, Ret>(SelectAll<LocalBoxStream<'a, Ret>>);
+
+impl<Ret> core::fmt::Debug for StreamTasks<'_, Ret> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "StreamTasks({})", self.0.len())
+    }
+}
+
+/// Tasks spawned with [`spawn_deadline`](LocalPool::spawn_deadline).
+#[derive(Debug)]
+struct DeadlineTasks<'a, Ret> {
+    tasks: Vec<DeadlineTask<'a, Ret>>,
+    ids: Slab,
+    /// Ids of the tasks dropped for missing their deadline, not yet taken.
+    timed_out: Vec<TaskId>,
+    /// Tasks dropped for missing their deadline so far, taken or not.
+    overdue: usize,
+}
+
+#[derive(Debug)]
+struct DeadlineTask<'a, Ret> {
+    future: LocalFutureObj<'a, Ret>,
+    deadline: Instant,
+    id: TaskId,
+    /// Whether a missed deadline is recorded in `timed_out`; tasks with a
+    /// [`DeadlineHandle`] report it through the handle instead.
+    report: bool,
+}
+
+impl<'a, Ret> DeadlineTasks<'a, Ret> {
+    fn new() -> Self {
+        Self { tasks: Vec::new(), ids: Slab::default(), timed_out: Vec::new(), overdue: 0 }
+    }
+
+    fn push(&mut self, future: LocalFutureObj<'a, Ret>, deadline: Instant, report: bool) -> TaskId {
+        let id = self.ids.insert();
+        self.tasks.push(DeadlineTask { future, deadline, id, report });
+        id
+    }
+
+    /// Polls every task until one completes, dropping the ones past their deadline.
+    ///
+    /// The deadline is checked after the poll, so a task that completes on the poll
+    /// during which its deadline passed still counts as completed.
+    fn poll(&mut self, cx: &mut Context<'_>, limit: Option<&TaskLimit>) -> Option<Ret> {
+        let mut i = 0;
+        while i < self.tasks.len() {
+            let task = &mut self.tasks[i];
+            if let Poll::Ready(ret) = task.future.poll_unpin(cx) {
+                let task = self.tasks.swap_remove(i);
+                self.ids.remove(task.id);
+                return Some(ret);
+            }
+            if Instant::now() >= task.deadline {
+                let task = self.tasks.swap_remove(i);
+                self.ids.remove(task.id);
+                if task.report {
+                    self.timed_out.push(task.id);
+                }
+                self.overdue += 1;
+                if let Some(limit) = limit {
+                    limit.release(1);
+                }
+            } else {
+                i += 1;
+            }
+        }
+        None
+    }
+
+    /// Takes every task out, without its deadline.
+    fn take(&mut self) -> Vec<LocalFutureObj<'a, Ret>> {
+        self.tasks.drain(..).map(|task| {
+            self.ids.remove(task.id);
+            task.future
+        }).collect()
+    }
+
+    fn clear(&mut self) -> usize {
+        self.take().len()
+    }
+}
+
+impl core::fmt::Debug for OnEmpty<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("OnEmpty")
+    }
 }
 
 
 #[derive(Clone)]
 pub struct Spawner<Ret> {
     tx: kanal::Sender<FutureObj<'static, Ret>>,
+    limit: Option<Arc<TaskLimit>>,
+    /// Set by [`shutdown`](LocalPool::shutdown).
+    closed: Arc<AtomicBool>,
 }
 
-impl<Ret> Spawner<Ret> {
+impl<Ret: 'static> Spawner<Ret> {
     pub fn spawn<F>(&self, f: F) -> Result<(), SpawnError>
         where F: UnsafeFutureObj<'static, Ret> + Send {
-        self.tx.send(FutureObj::new(f)).map_err(|_| SpawnError::shutdown())
+        self.send(FutureObj::new(f))
+    }
+
+    /// Like [`spawn`](Spawner::spawn), but hands `f` back if the pool turns it away.
+    ///
+    /// The pool is [`Full`](SpawnErrorKind::Full) once its
+    /// [task limit](LocalPool::with_task_limit) is reached.
+    ///
+    /// ```rust
+    /// use minimal_executor::{LocalPool, SpawnErrorKind};
+    ///
+    /// let mut pool = LocalPool::<u32>::new().with_task_limit(1);
+    /// let spawner = pool.spawner();
+    /// spawner.try_spawn(Box::pin(async { 1 })).unwrap();
+    /// let err = spawner.try_spawn(Box::pin(async { 2 })).unwrap_err();
+    /// assert_eq!(err.kind, SpawnErrorKind::Full);
+    ///
+    /// assert_eq!(pool.run(), vec![1]);
+    /// spawner.try_spawn(err.future).unwrap();
+    /// assert_eq!(pool.run(), vec![2]);
+    /// ```
+    pub fn try_spawn<F>(&self, f: F) -> Result<(), TrySpawnError<F>>
+        where F: UnsafeFutureObj<'static, Ret> + Send {
+        if self.tx.is_disconnected() {
+            return Err(TrySpawnError::new(SpawnErrorKind::Shutdown, f));
+        }
+        if let Err(kind) = self.admit() {
+            return Err(TrySpawnError::new(kind, f));
+        }
+        // a pool dropped since the check drops the task along with the ones it had
+        // already taken, as if it had gone a moment later
+        let _ = self.deliver(FutureObj::new(f));
+        Ok(())
+    }
+
+    /// Sends a task to the pool, adding it to the group of the tracked task that is
+    /// spawning it, if any.
+    fn send(&self, future: FutureObj<'static, Ret>) -> Result<(), SpawnError> {
+        self.admit().map_err(|_| SpawnError::shutdown())?;
+        self.deliver(future)
+    }
+
+    /// Checks the pool still takes tasks and claims a slot under its task limit.
+    fn admit(&self) -> Result<(), SpawnErrorKind> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpawnErrorKind::Shutdown);
+        }
+        if let Some(limit) = &self.limit {
+            if !limit.try_acquire() {
+                return Err(SpawnErrorKind::Full);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends a task that has been admitted, releasing its slot if the pool is gone.
+    fn deliver(&self, future: FutureObj<'static, Ret>) -> Result<(), SpawnError> {
+        let future = match track_current(future, pool_key(&self.closed)) {
+            Ok(tracked) => FutureObj::new(Box::pin(tracked)),
+            Err(future) => future,
+        };
+        self.tx.send(future).map_err(|_| {
+            if let Some(limit) = &self.limit {
+                limit.release(1);
+            }
+            SpawnError::shutdown()
+        })
     }
 }
 
 impl Spawn for Spawner<()> {
     fn spawn_obj(&self, future: FutureObj<'static, ()>) -> Result<(), SpawnError> {
-        self.tx.send(future).map_err(|_| SpawnError::shutdown())
+        self.send(future)
+    }
+}
+
+/// Lets a stream of futures be `forward`ed into the pool.
+///
+/// The channel is unbounded, so the sink is always ready and never needs flushing.
+impl<Ret: 'static> Sink<FutureObj<'static, Ret>> for Spawner<Ret> {
+    type Error = SpawnError;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), SpawnError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: FutureObj<'static, Ret>) -> Result<(), SpawnError> {
+        self.send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), SpawnError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), SpawnError>> {
+        Poll::Ready(Ok(()))
     }
 }
 
@@ -50,18 +274,191 @@ impl<'a, Ret> LocalPool<'a, Ret> {
     /// Create a new, empty pool of tasks.
     pub fn new() -> Self {
         let (tx, rx) = kanal::unbounded();
-        Self { pool: FuturesUnordered::new(), rx, tx }
+        Self {
+            pool: FuturesUnordered::new(),
+            rx,
+            tx,
+            local: LocalQueue::default(),
+            on_empty: None,
+            middleware: Middleware::new(),
+            streams: StreamTasks(SelectAll::new()),
+            deadlines: DeadlineTasks::new(),
+            limit: None,
+            closed: Arc::new(AtomicBool::new(false)),
+            awaited: Vec::new(),
+            completed: 0,
+        }
+    }
+
+    /// Install a spawn middleware layer, applied to every task spawned afterwards.
+    ///
+    /// The layer receives the task and returns the (possibly wrapped) future that is
+    /// actually stored, e.g. to add timing or tracing. Layers compose: each one wraps
+    /// the result of the layers installed before it.
+    pub fn with_middleware<F>(mut self, layer: F) -> Self
+        where F: FnMut(LocalFutureObj<'a, Ret>) -> LocalFutureObj<'a, Ret> + 'a {
+        self.middleware.push(layer);
+        self
+    }
+
+    /// Cap the pool at `max` tasks: running ones, ones with a deadline and ones its
+    /// spawners have queued.
+    ///
+    /// The cap is shared by every [`Spawner`] and [`LocalSpawner`] of the pool, so a task
+    /// that keeps spawning cannot grow it without bound. Once it is reached their `spawn`
+    /// fails and [`try_spawn`](Spawner::try_spawn) reports [`Full`](SpawnErrorKind::Full)
+    /// and hands the task back. Tasks spawned on the pool itself, with or without a
+    /// [deadline](LocalPool::spawn_deadline), are counted but never refused.
+    pub fn with_task_limit(mut self, max: usize) -> Self {
+        self.limit = Some(TaskLimit::new(max));
+        self
+    }
+
+    /// Register `f` to be called once, right after the task that leaves the pool
+    /// empty completes.
+    ///
+    /// Useful for signalling that a batch of spawned work has drained. Registering
+    /// again replaces a callback that has not fired yet.
+    pub fn on_empty<F>(&mut self, f: F)
+        where F: FnOnce() + 'a {
+        self.on_empty = Some(OnEmpty(Box::new(f)));
+    }
+
+    fn task_completed(&mut self) {
+        self.completed += 1;
+        if self.pool.is_empty() && self.streams.0.is_empty() && self.deadlines.tasks.is_empty() && self.pending_spawns() == 0 {
+            if let Some(OnEmpty(f)) = self.on_empty.take() {
+                f();
+            }
+        }
     }
 
     pub fn spawner(&self) -> Spawner<Ret> {
         Spawner {
-            tx: self.tx.clone()
+            tx: self.tx.clone(),
+            limit: self.limit.clone(),
+            closed: self.closed.clone(),
         }
     }
+    /// A handle for spawning `!Send` tasks onto the pool from its own thread.
+    pub fn local_spawner(&self) -> LocalSpawner<'a, Ret> {
+        LocalSpawner::new(&self.local, self.limit.clone(), self.closed.clone())
+    }
+    /// Number of unfinished tasks, counting each stream task once and including the
+    /// ones still waiting in the spawner channel.
+    pub fn len(&self) -> usize {
+        self.pool.len() + self.streams.0.len() + self.deadlines.tasks.len() + self.pending_spawns()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Number of tasks sent by spawners that the pool has not taken in yet.
+    pub fn pending_spawns(&self) -> usize {
+        self.rx.len() + self.local.borrow().len()
+    }
     pub fn spawn<F>(&mut self, f: F)
         where F: UnsafeFutureObj<'a, Ret> {
-        self.pool.push(LocalFutureObj::new(f))
+        if let Some(limit) = &self.limit {
+            limit.acquire();
+        }
+        let future = self.middleware.apply(LocalFutureObj::new(f));
+        self.pool.push(future)
+    }
+    /// Spawn a stream as a task whose every item is a result of the pool.
+    ///
+    /// The pool drives the stream to exhaustion; [`run`](LocalPool::run) collects its
+    /// items alongside the outputs of ordinary tasks.
+    pub fn spawn_stream_task<S>(&mut self, s: S)
+        where S: Stream<Item = Ret> + 'a {
+        self.streams.0.push(s.boxed_local());
+    }
+
+    /// Spawn `f` with a soft deadline and return the id it is reported under if it
+    /// misses it.
+    ///
+    /// Unlike other tasks, deadline tasks are polled on every
+    /// [`poll_once`](LocalPool::poll_once) whether or not they were woken, so their
+    /// deadline is checked even while they wait. A task still pending after a poll
+    /// that ended past `deadline` is dropped and its id recorded as timed out; take
+    /// those with [`take_timed_out`](LocalPool::take_timed_out), or run the pool with
+    /// [`run_with_deadlines`](LocalPool::run_with_deadlines). A task that completes on
+    /// that last poll counts as completed.
+    pub fn spawn_deadline<F>(&mut self, f: F, deadline: Instant) -> TaskId
+        where F: UnsafeFutureObj<'a, Ret> {
+        self.push_deadline(LocalFutureObj::new(f), deadline, true)
+    }
+
+    fn push_deadline(&mut self, future: LocalFutureObj<'a, Ret>, deadline: Instant, report: bool) -> TaskId {
+        if let Some(limit) = &self.limit {
+            limit.acquire();
+        }
+        let future = self.middleware.apply(future);
+        self.deadlines.push(future, deadline, report)
+    }
+
+    /// Takes the ids of the tasks that missed their deadline since the last call.
+    pub fn take_timed_out(&mut self) -> Vec<TaskId> {
+        core::mem::take(&mut self.deadlines.timed_out)
+    }
+
+    /// Like [`run`](LocalPool::run), but also returns the ids of the tasks spawned with
+    /// [`spawn_deadline`](LocalPool::spawn_deadline) that missed their deadline.
+    ///
+    /// ```rust
+    /// use std::time::{Duration, Instant};
+    /// use minimal_executor::LocalPool;
+    ///
+    /// let mut pool = LocalPool::new();
+    /// pool.spawn_deadline(Box::pin(async { 1 }), Instant::now() + Duration::from_secs(1));
+    /// let late = pool.spawn_deadline(Box::pin(futures::future::pending()), Instant::now());
+    /// assert_eq!(pool.run_with_deadlines(), (vec![1], vec![late]));
+    /// ```
+    pub fn run_with_deadlines(&mut self) -> (Vec<Ret>, Vec<TaskId>) {
+        let results = self.run();
+        (results, self.take_timed_out())
+    }
+    /// Spawn `fut` and return a handle that resolves once it and every task it spawns
+    /// through a [`Spawner`] or [`LocalSpawner`] of this pool while being polled,
+    /// transitively, have completed. Tasks it spawns into other pools are not waited for.
+    ///
+    /// Dropped tasks count as completed, so the handle also resolves if the pool is
+    /// dropped.
+    pub fn spawn_tracked<Fut>(&mut self, fut: Fut) -> CompletionHandle
+        where Fut: Future<Output = Ret> + 'a {
+        let (task, handle) = track_root(Box::pin(fut), pool_key(&self.closed));
+        self.spawn(Box::pin(task));
+        handle
+    }
+    /// Spawn a fused future, skipping it if it has already terminated.
+    ///
+    /// A terminated future usually means a completed one was accidentally reused: debug
+    /// builds panic on it, release builds skip it and return `false`.
+    pub fn spawn_checked<F>(&mut self, f: F) -> bool
+        where F: FusedFuture<Output = Ret> + 'a {
+        debug_assert!(!f.is_terminated(), "spawned a future that already terminated");
+        if f.is_terminated() {
+            return false;
+        }
+        self.spawn(Box::pin(f));
+        true
+    }
+    /// Like [`run`](LocalPool::run), but returns a [`StuckError`](crate::StuckError)
+    /// once `max_idle_rounds` passes in a row completed no task, instead of spinning
+    /// forever on tasks that never complete.
+    ///
+    /// See [`PoolExt::run_bounded`](crate::PoolExt::run_bounded).
+    pub fn run_bounded(&mut self, max_idle_rounds: usize) -> Result<alloc::vec::Vec<Ret>, crate::StuckError> {
+        drop_on_panic(self, |pool| crate::PoolExt::run_bounded(pool, max_idle_rounds), Self::clear)
     }
+
+    /// Like [`run`](LocalPool::run), but hands out the results one at a time as tasks
+    /// complete, so the caller can stop early and leave the rest in the pool.
+    ///
+    /// See [`RunIter`](crate::RunIter).
+    pub fn run_iter(&mut self) -> crate::RunIter<'_, Self> {
+        crate::RunIter::new(self)
+    }
+
     /// Run all tasks in the pool to completion.
     ///
     /// ```rust
@@ -77,20 +474,361 @@ impl<'a, Ret> LocalPool<'a, Ret> {
     /// ```
     ///
     /// The function will block the calling thread until *all* tasks in the pool
-    /// are complete, including any spawned while running existing tasks.
+    /// are complete, including any spawned while running existing tasks. With the `std`
+    /// feature, a panicking task drops every task left in the pool as the panic unwinds
+    /// out of this call.
     pub fn run(&mut self) -> alloc::vec::Vec<Ret> {
-        let mut results = alloc::vec::Vec::new();
-        loop {
-            let ret = self.poll_once();
+        self.run_fold(alloc::vec::Vec::new(), |mut results, r| {
+            results.push(r);
+            results
+        })
+    }
 
-            // no queued tasks; we may be done
-            match ret {
-                Poll::Pending => {}
-                Poll::Ready(None) => break,
-                Poll::Ready(Some(r)) => { results.push(r); }
+    /// Stop accepting tasks from spawners, then run every task left to completion and
+    /// return the results.
+    ///
+    /// From then on, spawners get a shutdown [`SpawnError`], including the ones held by
+    /// tasks still running, so work spawned during the wind-down is rejected rather than
+    /// run. A spawn racing this call may still be accepted and is run. Tasks spawned
+    /// directly on the pool are not affected.
+    ///
+    /// ```rust
+    /// use minimal_executor::LocalPool;
+    ///
+    /// let mut pool = LocalPool::new();
+    /// let spawner = pool.spawner();
+    /// spawner.spawn(Box::pin(async { 1 })).unwrap();
+    /// assert_eq!(pool.shutdown(), vec![1]);
+    /// assert!(spawner.spawn(Box::pin(async { 2 })).is_err());
+    /// ```
+    pub fn shutdown(&mut self) -> Vec<Ret> {
+        self.closed.store(true, Ordering::Release);
+        self.run()
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Run all tasks to completion, folding each result into an accumulator as it
+    /// completes instead of collecting them.
+    ///
+    /// ```rust
+    /// use minimal_executor::LocalPool;
+    ///
+    /// let mut pool = LocalPool::new();
+    /// for i in 1..=3 {
+    ///     pool.spawn(Box::pin(async move { i }));
+    /// }
+    /// assert_eq!(pool.run_fold(0, |sum, i| sum + i), 6);
+    /// ```
+    pub fn run_fold<B, F: FnMut(B, Ret) -> B>(&mut self, init: B, mut f: F) -> B {
+        drop_on_panic(self, |pool| {
+            let mut acc = init;
+            loop {
+                let ret = pool.poll_once();
+
+                // no queued tasks; we may be done
+                match ret {
+                    Poll::Pending => {}
+                    Poll::Ready(None) => break,
+                    Poll::Ready(Some(r)) => { acc = f(acc, r); }
+                }
+            }
+            acc
+        }, Self::clear)
+    }
+
+    /// Run all tasks to completion, handing each result to `on_result` as it completes.
+    ///
+    /// Nothing is collected, so this does not allocate for the results.
+    ///
+    /// ```rust
+    /// use minimal_executor::LocalPool;
+    ///
+    /// let mut pool = LocalPool::new();
+    /// for i in 1..=3 {
+    ///     pool.spawn(Box::pin(async move { i }));
+    /// }
+    /// let mut max = 0;
+    /// pool.run_with(|i| max = max.max(i));
+    /// assert_eq!(max, 3);
+    /// ```
+    pub fn run_with<F: FnMut(Ret)>(&mut self, mut on_result: F) {
+        self.run_fold((), |(), r| on_result(r))
+    }
+
+    /// Run tasks until every one has completed or the pool stalls, then take the
+    /// stalled futures out of the pool.
+    ///
+    /// Returns the completed results and the futures that never finished, which the
+    /// caller can inspect or re-home on another executor. Tasks spawned with a deadline
+    /// are returned as well, without their deadline; stalled stream tasks stay in the
+    /// pool.
+    pub fn run_recovering(&mut self) -> (Vec<Ret>, Vec<LocalFutureObj<'a, Ret>>) {
+        let mut results = Vec::new();
+        self.drive_until_stalled(|r| {
+            results.push(r);
+            true
+        });
+        let mut stalled: Vec<_> = core::mem::take(&mut self.pool).into_iter().collect();
+        stalled.extend(self.deadlines.take());
+        if let Some(limit) = &self.limit {
+            limit.release(stalled.len());
+        }
+        (results, stalled)
+    }
+
+    /// Borrow the pool as a task for another executor, resolving to every result once
+    /// the pool is empty.
+    ///
+    /// Same as awaiting `&mut pool`: each poll advances the pool with the outer
+    /// context, so wake-ups inside the pool wake the task holding it.
+    ///
+    /// ```rust
+    /// use minimal_executor::LocalPool;
+    ///
+    /// let mut inner = LocalPool::new();
+    /// inner.spawn(Box::pin(async { 1 }));
+    /// let mut outer = LocalPool::new();
+    /// outer.spawn(Box::pin(inner.as_task()));
+    /// assert_eq!(outer.run(), vec![vec![1]]);
+    /// ```
+    pub fn as_task(&mut self) -> impl Future<Output = Vec<Ret>> + use<'_, 'a, Ret> {
+        self
+    }
+
+    /// Stream the results of tasks as they complete, ending once the pool is empty.
+    ///
+    /// Every poll of the stream polls the pool once, picking up tasks spawned in the
+    /// meantime. While no task completes the stream wakes itself, so it keeps the pool
+    /// running when driven by another executor. Unconsumed tasks stay in the pool.
+    ///
+    /// ```rust
+    /// use futures::StreamExt;
+    /// use minimal_executor::LocalPool;
+    ///
+    /// let mut pool = LocalPool::new();
+    /// pool.spawn(Box::pin(async { 2 }));
+    /// let sum = minimal_executor::block_on(pool.stream().fold(0, |sum, i| async move { sum + i }));
+    /// assert_eq!(sum, 2);
+    /// ```
+    pub fn stream(&mut self) -> impl Stream<Item = Ret> + use<'_, 'a, Ret> {
+        futures::stream::poll_fn(move |cx| match self.poll_once() {
+            Poll::Ready(ret) => Poll::Ready(ret),
+            Poll::Pending => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+    }
+
+    /// Drive the pool's tasks until `f` completes, and return its output.
+    ///
+    /// `f` is polled once per pass over the tasks, before the tasks themselves. Tasks
+    /// still pending when `f` completes stay in the pool; the results of tasks that
+    /// completed in the meantime are dropped.
+    ///
+    /// ```rust
+    /// use minimal_executor::LocalPool;
+    ///
+    /// let mut pool = LocalPool::new();
+    /// pool.spawn(Box::pin(async {}));
+    /// assert_eq!(pool.run_until(async { 3 }), 3);
+    /// ```
+    pub fn run_until<F: Future>(&mut self, f: F) -> F::Output {
+        drop_on_panic(self, |pool| {
+            futures::pin_mut!(f);
+            loop {
+                if let Poll::Ready(t) = poll_fn(|cx| f.as_mut().poll(cx)) {
+                    return t;
+                }
+                let _ = pool.poll_once();
             }
+        }, Self::clear)
+    }
+
+    /// Like [`run`](LocalPool::run), but also polls `reactor` once per pass over the tasks.
+    ///
+    /// The reactor is the pool's event source, e.g. a future driving an epoll loop that
+    /// wakes the tasks waiting on I/O. It is not expected to complete; the call returns
+    /// once the pool is empty, and a reactor that does finish is simply not polled again.
+    pub fn run_with_reactor<R: Future<Output = ()>>(&mut self, reactor: R) -> Vec<Ret> {
+        drop_on_panic(self, |pool| {
+            let reactor = reactor.fuse();
+            futures::pin_mut!(reactor);
+            let mut results = Vec::new();
+            loop {
+                match pool.poll_once() {
+                    Poll::Pending => {}
+                    Poll::Ready(None) => break,
+                    Poll::Ready(Some(r)) => results.push(r),
+                }
+                if !reactor.is_terminated() {
+                    let _ = poll_fn(|cx| reactor.as_mut().poll(cx));
+                }
+            }
+            results
+        }, Self::clear)
+    }
+
+    /// Iterate over results as tasks complete, driving the pool on each `next`.
+    ///
+    /// The iterator ends once the pool is empty or stalls; use
+    /// [`Results::is_stalled`] to tell the two apart. Unconsumed tasks stay in the pool.
+    pub fn results(&mut self) -> Results<'_, 'a, Ret> {
+        Results { pool: self }
+    }
+
+    /// Like [`run`](LocalPool::run), but sleeps between polls while the pool is idle.
+    ///
+    /// Tasks are polled with a waker that unparks the running thread, so as long as
+    /// tasks keep completing or waking each other the pool is polled tightly. Once a
+    /// poll finds nothing ready, the thread parks for a timeout that doubles with every
+    /// idle poll, up to a few milliseconds, to also catch tasks sent by spawners, which
+    /// do not wake the thread. Any completion resets the timeout.
+    pub fn run_adaptive(&mut self) -> Vec<Ret> {
+        const MIN_BACKOFF: Duration = Duration::from_micros(10);
+        const MAX_BACKOFF: Duration = Duration::from_millis(5);
+
+        drop_on_panic(self, |pool| {
+            let wake = TaskWake::new(WakerStrategy::Parking);
+            let waker = futures::task::waker_ref(&wake);
+            let mut cx = Context::from_waker(&waker);
+            let mut results = Vec::new();
+            let mut backoff = Duration::ZERO;
+            loop {
+                wake.read_reset();
+                pool.ingest();
+                match pool.poll_tasks(&mut cx) {
+                    Poll::Ready(Some(r)) => {
+                        pool.task_completed();
+                        results.push(r);
+                        backoff = Duration::ZERO;
+                    }
+                    Poll::Ready(None) => return results,
+                    Poll::Pending => {
+                        if wake.read_reset() || pool.pending_spawns() > 0 {
+                            continue;
+                        }
+                        backoff = (backoff * 2).clamp(MIN_BACKOFF, MAX_BACKOFF);
+                        std::thread::park_timeout(backoff);
+                    }
+                }
+            }
+        }, Self::clear)
+    }
+
+    /// Drive the pool until any task completes and return its result.
+    ///
+    /// The other tasks stay queued. Returns `None` if the pool is empty or stalls
+    /// before anything completes.
+    pub fn run_until_any(&mut self) -> Option<Ret> {
+        let mut first = None;
+        self.drive_until_stalled(|r| {
+            first = Some(r);
+            false
+        });
+        first
+    }
+
+    /// Polls until the pool is empty, stalls, or `on_ready` returns `false`.
+    ///
+    /// The pool counts as stalled once a poll completes nothing, no task has been
+    /// woken since, and no spawns are waiting in the channel.
+    fn drive_until_stalled(&mut self, mut on_ready: impl FnMut(Ret) -> bool) {
+        drop_on_panic(self, |pool| {
+            let wake = TaskWake::new(WakerStrategy::SingleFlag);
+            let waker = futures::task::waker_ref(&wake);
+            let mut cx = Context::from_waker(&waker);
+            loop {
+                wake.read_reset();
+                pool.ingest();
+                match pool.poll_tasks(&mut cx) {
+                    Poll::Ready(Some(r)) => {
+                        pool.task_completed();
+                        if !on_ready(r) {
+                            return;
+                        }
+                    }
+                    Poll::Ready(None) => return,
+                    Poll::Pending => {
+                        if !wake.read_reset() && pool.pending_spawns() == 0 {
+                            return;
+                        }
+                    }
+                }
+            }
+        }, Self::clear)
+    }
+
+    /// Drops every task, including the ones still waiting in the spawner channel.
+    fn clear(&mut self) {
+        let mut dropped = self.pool.len() + self.deadlines.clear();
+        self.pool.clear();
+        self.streams = StreamTasks(SelectAll::new());
+        while self.rx.try_recv().ok().flatten().is_some() {
+            dropped += 1;
+        }
+        dropped += core::mem::take(&mut *self.local.borrow_mut()).len();
+        if let Some(limit) = &self.limit {
+            limit.release(dropped);
         }
-        results
+    }
+
+    /// Moves the tasks sent by spawners into the pool and returns how many there were.
+    fn ingest(&mut self) -> usize {
+        let mut ingested = 0;
+        while let Some(fut) = self.rx.try_recv().ok().flatten() {
+            let fut = self.middleware.apply(LocalFutureObj::from(fut));
+            self.pool.push(fut);
+            ingested += 1;
+        }
+        let local = core::mem::take(&mut *self.local.borrow_mut());
+        for fut in local {
+            let fut = self.middleware.apply(fut);
+            self.pool.push(fut);
+            ingested += 1;
+        }
+        ingested
+    }
+
+    /// Polls the future tasks, then the stream tasks. Returns `Ready(None)` only once
+    /// both are empty.
+    fn poll_tasks(&mut self, cx: &mut Context<'_>) -> Poll<Option<Ret>> {
+        let tasks = self.pool.poll_next_unpin(cx);
+        if let Poll::Ready(Some(r)) = tasks {
+            if let Some(limit) = &self.limit {
+                limit.release(1);
+            }
+            return Poll::Ready(Some(r));
+        }
+        if let Some(r) = self.deadlines.poll(cx, self.limit.as_deref()) {
+            if let Some(limit) = &self.limit {
+                limit.release(1);
+            }
+            return Poll::Ready(Some(r));
+        }
+        match self.streams.0.poll_next_unpin(cx) {
+            Poll::Ready(None) if !self.deadlines.tasks.is_empty() => Poll::Pending,
+            Poll::Ready(None) => tasks,
+            ret => ret,
+        }
+    }
+
+    /// Run all tasks in the pool to completion, sending each result on `tx` as soon
+    /// as its task finishes.
+    ///
+    /// If the receiving side hangs up, driving stops and the undelivered result is
+    /// returned in the error; the remaining tasks stay in the pool.
+    pub fn run_into_channel(&mut self, tx: crossbeam::channel::Sender<Ret>) -> Result<(), crossbeam::channel::SendError<Ret>> {
+        drop_on_panic(self, |pool| loop {
+            match pool.poll_once() {
+                Poll::Pending => {}
+                Poll::Ready(None) => return Ok(()),
+                Poll::Ready(Some(r)) => tx.send(r)?,
+            }
+        }, Self::clear)
     }
 
     /// Runs all tasks and returns after completing one future or until no more progress
@@ -135,14 +873,230 @@ impl<'a, Ret> LocalPool<'a, Ret> {
         }
     }
 
+    /// Run tasks until `max` of them have completed or none of the rest can make
+    /// progress, returning the results in completion order.
+    ///
+    /// Sits between [`try_run_one`](LocalPool::try_run_one) and [`run`](LocalPool::run):
+    /// it stops at the first [`try_run_one`](LocalPool::try_run_one) that completes
+    /// nothing, so pending tasks make it return early instead of spinning. Unfinished
+    /// tasks stay in the pool.
+    pub fn run_n(&mut self, max: usize) -> alloc::vec::Vec<Ret> {
+        drop_on_panic(self, |pool| {
+            let mut results = alloc::vec::Vec::new();
+            while results.len() < max {
+                match pool.try_run_one() {
+                    Poll::Ready(r) => results.push(r),
+                    Poll::Pending => break,
+                }
+            }
+            results
+        }, Self::clear)
+    }
+
 
     pub fn poll_once(&mut self) -> Poll<Option<Ret>> {
-        poll_fn(|cx| {
-            while let Some(fut) = self.rx.try_recv().ok().flatten() {
-                self.pool.push(LocalFutureObj::from(fut))
+        self.poll_once_ingest().1
+    }
+
+    /// Runs every task that can complete right now, discarding the results, and returns
+    /// `Ready(())` once the pool is empty.
+    ///
+    /// Never waits, so it can be called from an event loop that must not block, e.g. a
+    /// `requestAnimationFrame` or `queueMicrotask` callback in a browser; see
+    /// `spawn_on_microtask` (with the `wasm` feature) to reschedule it on wake-ups instead.
+    ///
+    /// ```rust
+    /// use core::task::Poll;
+    /// use minimal_executor::LocalPool;
+    ///
+    /// let mut pool = LocalPool::new();
+    /// pool.spawn(Box::pin(async {}));
+    /// assert_eq!(pool.poll_step(), Poll::Ready(()));
+    /// ```
+    pub fn poll_step(&mut self) -> Poll<()> {
+        loop {
+            match self.poll_once() {
+                Poll::Ready(Some(_)) => {}
+                Poll::Ready(None) => return Poll::Ready(()),
+                Poll::Pending => return Poll::Pending,
             }
-            self.pool.poll_next_unpin(cx)
-        })
+        }
+    }
+
+    /// Like [`poll_once`](LocalPool::poll_once), but polls the tasks with `cx`, so the
+    /// wakers they register wake the caller.
+    ///
+    /// This lets the pool be driven from another executor or reactor; `Pending` means
+    /// `cx` will be woken once a task can make progress.
+    pub fn poll_with(&mut self, cx: &mut Context<'_>) -> Poll<Option<Ret>> {
+        self.ingest();
+        let ret = self.poll_tasks(cx);
+        if let Poll::Ready(Some(_)) = ret {
+            self.task_completed();
+        }
+        ret
+    }
+
+    /// Like [`poll_once`](LocalPool::poll_once), but also returns how many tasks were
+    /// taken from the spawner channel during this call.
+    ///
+    /// A non-zero count means spawners are still feeding the pool.
+    pub fn poll_once_ingest(&mut self) -> (usize, Poll<Option<Ret>>) {
+        let (ingested, ret) = poll_fn(|cx| (self.ingest(), self.poll_tasks(cx)));
+        if let Poll::Ready(Some(_)) = ret {
+            self.task_completed();
+        }
+        (ingested, ret)
+    }
+}
+
+/// Awaiting the pool runs it to completion with the caller's context, resolving to
+/// every result once the pool is empty.
+///
+/// Tasks are polled with the caller's waker, so the outer executor is woken whenever a
+/// task is. Tasks sent by a [`Spawner`] while the pool is waiting do not wake it; they
+/// are picked up the next time the pool is polled.
+///
+/// ```rust
+/// use minimal_executor::LocalPool;
+///
+/// let mut pool = LocalPool::new();
+/// pool.spawn(Box::pin(async { 1 }));
+/// assert_eq!(futures::executor::block_on(&mut pool), vec![1]);
+/// ```
+impl<'a, Ret> Future for &mut LocalPool<'a, Ret> {
+    type Output = Vec<Ret>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Vec<Ret>> {
+        let pool = &mut **self.get_mut();
+        loop {
+            match pool.poll_with(cx) {
+                Poll::Ready(Some(r)) => pool.awaited.push(r),
+                Poll::Ready(None) => return Poll::Ready(core::mem::take(&mut pool.awaited)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<'a, T: 'a, E: 'a> LocalPool<'a, Result<T, E>> {
+    /// Spawn a fallible task that is retried up to `retries` times.
+    ///
+    /// `factory` creates a fresh future for every attempt. The task completes with the
+    /// first `Ok`, or with the `Err` of the last attempt once the retries are used up.
+    pub fn spawn_retry<F, Fut>(&mut self, mut factory: F, retries: usize)
+        where F: FnMut() -> Fut + 'a, Fut: Future<Output = Result<T, E>> + 'a {
+        self.spawn(Box::pin(async move {
+            let mut attempt = 0;
+            loop {
+                match factory().await {
+                    Err(_) if attempt < retries => attempt += 1,
+                    ret => return ret,
+                }
+            }
+        }));
+    }
+}
+
+impl<'a, K: Eq + Hash + 'a, T: 'a> LocalPool<'a, (K, T)> {
+    /// Spawn a task whose result is filed under `key` by [`run_grouped`](LocalPool::run_grouped).
+    pub fn spawn_keyed<Fut>(&mut self, key: K, fut: Fut)
+        where Fut: Future<Output = T> + 'a {
+        self.spawn(Box::pin(fut.map(move |ret| (key, ret))));
+    }
+
+    /// Run all tasks to completion and bucket their results by key.
+    pub fn run_grouped(&mut self) -> HashMap<K, Vec<T>> {
+        let mut groups = HashMap::new();
+        for (key, ret) in self.run() {
+            groups.entry(key).or_insert_with(Vec::new).push(ret);
+        }
+        groups
+    }
+}
+
+/// A one-line summary for logs, e.g. `pool: 3 tasks, 1024 completed, 2 overdue`, with
+/// the [task limit](LocalPool::with_task_limit) as capacity if there is one.
+impl<Ret> core::fmt::Display for LocalPool<'_, Ret> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let cap = self.limit.as_ref().map(|l| l.max());
+        crate::write_summary(f, self.len(), cap, self.completed, Some(self.deadlines.overdue))
+    }
+}
+
+impl<'a> LocalPool<'a, ()> {
+    /// Spawn `fut` once and return a cloneable handle to its result.
+    ///
+    /// The pool drives the underlying future a single time; every clone of the
+    /// returned [`Shared`] resolves to a clone of the same output.
+    pub fn spawn_shared<Fut>(&mut self, fut: Fut) -> Shared<Fut>
+        where Fut: Future + 'a, Fut::Output: Clone {
+        let shared = fut.shared();
+        self.spawn(Box::pin(shared.clone().map(drop)));
+        shared
+    }
+
+    /// Spawn `fut` and hand its output to `sink` when it completes.
+    ///
+    /// This lets individual tasks route their results to different destinations
+    /// instead of having them collected by `run`.
+    pub fn spawn_to_sink<Fut, S>(&mut self, fut: Fut, sink: S)
+        where Fut: Future + 'a, S: FnOnce(Fut::Output) + 'a {
+        self.spawn(Box::pin(fut.map(sink)));
+    }
+
+    /// Spawn `f` so that it can be cancelled through the returned [`AbortHandle`].
+    ///
+    /// Once aborted, the task is dropped the next time the pool polls it, without
+    /// polling `f` again, and the [`JoinHandle`] resolves to `Err(Canceled)`. Aborting
+    /// a task that has already completed does nothing.
+    pub fn spawn_abortable<F>(&mut self, f: F) -> (AbortHandle, JoinHandle<F::Output>)
+        where F: Future + 'a {
+        let (task, abort, handle) = with_abort_handle(f);
+        self.spawn(Box::pin(task));
+        (abort, handle)
+    }
+
+    /// Spawn `f` for its side effects, discarding its output.
+    pub fn spawn_detached<F>(&mut self, f: F)
+        where F: Future + 'a {
+        self.spawn(Box::pin(f.map(drop)));
+    }
+
+    /// Spawn `fut` with a deadline and return a handle resolving to its output.
+    ///
+    /// The task is polled and its deadline checked like one spawned with
+    /// [`spawn_deadline`](LocalPool::spawn_deadline), even while it waits without being
+    /// woken. If it is still pending after a poll that ended past `deadline`, it is
+    /// dropped and the handle resolves to `Err(Timeout)`; its id is not recorded as
+    /// timed out.
+    pub fn spawn_with_deadline_handle<Fut>(&mut self, fut: Fut, deadline: Instant) -> DeadlineHandle<Fut::Output>
+        where Fut: Future + 'a {
+        let (task, handle) = with_handle(fut);
+        self.push_deadline(LocalFutureObj::new(Box::pin(task)), deadline, false);
+        handle
+    }
+}
+
+/// Iterator over the results of a pool, see [`results`](LocalPool::results).
+#[derive(Debug)]
+pub struct Results<'p, 'a, Ret> {
+    pool: &'p mut LocalPool<'a, Ret>,
+}
+
+impl<Ret> Results<'_, '_, Ret> {
+    /// Whether tasks are left that cannot currently make progress.
+    pub fn is_stalled(&self) -> bool {
+        !self.pool.pool.is_empty() || !self.pool.streams.0.is_empty()
+            || !self.pool.deadlines.tasks.is_empty() || self.pool.pending_spawns() > 0
+    }
+}
+
+impl<Ret> Iterator for Results<'_, '_, Ret> {
+    type Item = Ret;
+
+    fn next(&mut self) -> Option<Ret> {
+        self.pool.run_until_any()
     }
 }
 
@@ -0,0 +1,80 @@
+use alloc::rc::{Rc, Weak};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+use futures::future::LocalFutureObj;
+use futures::task::{LocalSpawn, SpawnError, UnsafeFutureObj};
+use crate::limit::TaskLimit;
+#[cfg(feature = "std")]
+use crate::tracked::{pool_key, track_current};
+#[cfg(feature = "std")]
+use alloc::boxed::Box;
+
+/// Tasks queued by the [`LocalSpawner`]s of a pool, in spawn order.
+pub(crate) type LocalQueue<'a, Ret> = Rc<RefCell<Vec<LocalFutureObj<'a, Ret>>>>;
+
+/// A cloneable handle for spawning `!Send` tasks onto the pool that created it.
+///
+/// The pool's `Spawner` can be sent to other threads, so it only takes `Send` futures.
+/// This handle stays on the pool's thread instead, which lets it take any future and
+/// implement [`LocalSpawn`]. Tasks are queued until the pool's next poll, and are
+/// subject to the same task limit and shutdown as the pool's `Spawner`.
+///
+/// ```rust
+/// use std::rc::Rc;
+/// use futures::task::LocalSpawnExt;
+/// use minimal_executor::LocalPool;
+///
+/// let mut pool = LocalPool::new();
+/// let rc = Rc::new(5);
+/// pool.local_spawner().spawn_local(async move { assert_eq!(*rc, 5) }).unwrap();
+/// assert_eq!(pool.run().len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LocalSpawner<'a, Ret> {
+    queue: Weak<RefCell<Vec<LocalFutureObj<'a, Ret>>>>,
+    limit: Option<Arc<TaskLimit>>,
+    closed: Arc<AtomicBool>,
+}
+
+impl<'a, Ret> LocalSpawner<'a, Ret> {
+    pub(crate) fn new(queue: &LocalQueue<'a, Ret>, limit: Option<Arc<TaskLimit>>, closed: Arc<AtomicBool>) -> Self {
+        Self { queue: Rc::downgrade(queue), limit, closed }
+    }
+}
+
+impl<'a, Ret: 'a> LocalSpawner<'a, Ret> {
+
+    pub fn spawn<F>(&self, f: F) -> Result<(), SpawnError>
+        where F: UnsafeFutureObj<'a, Ret> {
+        self.send(LocalFutureObj::new(f))
+    }
+
+    /// Queues a task for the pool, unless the pool is gone, shut down or at its task limit.
+    fn send(&self, future: LocalFutureObj<'a, Ret>) -> Result<(), SpawnError> {
+        let queue = self.queue.upgrade().ok_or(SpawnError::shutdown())?;
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpawnError::shutdown());
+        }
+        if let Some(limit) = &self.limit {
+            if !limit.try_acquire() {
+                return Err(SpawnError::shutdown());
+            }
+        }
+        // `!Send` children of a tracked task join its group, like those sent by a `Spawner`
+        #[cfg(feature = "std")]
+        let future = match track_current(future, pool_key(&self.closed)) {
+            Ok(tracked) => LocalFutureObj::new(Box::pin(tracked)),
+            Err(future) => future,
+        };
+        queue.borrow_mut().push(future);
+        Ok(())
+    }
+}
+
+impl LocalSpawn for LocalSpawner<'static, ()> {
+    fn spawn_local_obj(&self, future: LocalFutureObj<'static, ()>) -> Result<(), SpawnError> {
+        self.send(future)
+    }
+}
@@ -0,0 +1,37 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use futures::future::LocalFutureObj;
+
+type Layer<'a, Ret> = Box<dyn FnMut(LocalFutureObj<'a, Ret>) -> LocalFutureObj<'a, Ret> + 'a>;
+
+/// The stack of spawn middleware installed on a pool with `with_middleware`.
+///
+/// Each layer receives the task (already wrapped by the layers installed before it)
+/// and returns the future the pool actually stores.
+pub(crate) struct Middleware<'a, Ret> {
+    layers: Vec<Layer<'a, Ret>>,
+}
+
+impl<'a, Ret> Middleware<'a, Ret> {
+    pub fn new() -> Self {
+        Self { layers: Vec::new() }
+    }
+    pub fn push<F>(&mut self, layer: F)
+        where F: FnMut(LocalFutureObj<'a, Ret>) -> LocalFutureObj<'a, Ret> + 'a {
+        self.layers.push(Box::new(layer))
+    }
+    pub fn apply(&mut self, mut fut: LocalFutureObj<'a, Ret>) -> LocalFutureObj<'a, Ret> {
+        for layer in &mut self.layers {
+            fut = layer(fut);
+        }
+        fut
+    }
+}
+
+impl<Ret> core::fmt::Debug for Middleware<'_, Ret> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Middleware")
+            .field("layers", &self.layers.len())
+            .finish()
+    }
+}
@@ -0,0 +1,41 @@
+use alloc::boxed::Box;
+use crate::slab::TaskId;
+
+/// What a poll of a task returned, as reported to a poll observer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollState {
+    Ready,
+    Pending,
+}
+
+type Callback<'a> = Box<dyn FnMut(TaskId, PollState) + 'a>;
+
+/// The callback installed with `set_poll_observer`, if any.
+pub(crate) struct PollObserver<'a> {
+    callback: Option<Callback<'a>>,
+}
+
+impl<'a> PollObserver<'a> {
+    pub fn new() -> Self {
+        Self { callback: None }
+    }
+    pub fn set<F: FnMut(TaskId, PollState) + 'a>(&mut self, f: F) {
+        self.callback = Some(Box::new(f));
+    }
+    pub fn is_set(&self) -> bool {
+        self.callback.is_some()
+    }
+    pub fn notify(&mut self, id: TaskId, state: PollState) {
+        if let Some(callback) = &mut self.callback {
+            callback(id, state);
+        }
+    }
+}
+
+impl core::fmt::Debug for PollObserver<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PollObserver")
+            .field("set", &self.is_set())
+            .finish()
+    }
+}
@@ -0,0 +1,18 @@
+//! The types, functions and traits most code needs, for a single glob import.
+//!
+//! ```rust
+//! use minimal_executor::prelude::*;
+//!
+//! let mut pool = NewLocalPool::new();
+//! pool.local_spawner().spawn_local(async {}).unwrap();
+//! assert_eq!(pool.run_until(async { 1 }), 1);
+//! ```
+#[cfg(feature = "std")]
+pub use crate::{LocalPool, Spawner};
+pub use crate::{BusyLocalPool, BusySpawner, LifoLocalPool, LifoSpawner, NewLocalPool, NewSpawner, LocalSpawner};
+pub use crate::{JoinHandle, TaskId, WakerStrategy};
+pub use crate::{block_fn_hot, poll_all, poll_fn, poll_on, scope, yield_now};
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub use crate::{block_fn, block_on, block_on_in_pool};
+pub use crate::ext::PoolExt;
+pub use futures::task::{LocalSpawn, LocalSpawnExt, Spawn, SpawnExt};
@@ -0,0 +1,153 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam::queue::{ArrayQueue, SegQueue};
+
+/// A queue backend for the busy pool.
+///
+/// The pool and its spawners share the queue and only ever hold `&self`, so
+/// implementations need interior mutability. The pool never keeps more items than
+/// [`capacity`](TaskQueue::capacity) in the queue, counting the ones it popped to poll,
+/// and relies on `push` accepting an item whenever it stays within that limit.
+///
+/// ```rust
+/// use crossbeam::queue::ArrayQueue;
+/// use minimal_executor::{BusyLocalPool, WakerStrategy};
+///
+/// let mut pool = BusyLocalPool::with_queues(ArrayQueue::new(4), ArrayQueue::new(1), WakerStrategy::Busy);
+/// pool.spawn(Box::pin(async { 1 })).unwrap();
+/// assert_eq!(pool.run(), vec![1]);
+/// ```
+pub trait TaskQueue<T> {
+    /// Pushes `value` at the back, handing it back if the queue is full.
+    fn push(&self, value: T) -> Result<(), T>;
+    /// Pops the value at the front.
+    fn pop(&self) -> Option<T>;
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// How many values the pool may keep in the queue.
+    fn capacity(&self) -> usize;
+    /// Raises the capacity, returning `false` if it cannot grow any further.
+    fn grow(&self) -> bool {
+        false
+    }
+}
+
+impl<T> TaskQueue<T> for ArrayQueue<T> {
+    fn push(&self, value: T) -> Result<(), T> {
+        ArrayQueue::push(self, value)
+    }
+
+    fn pop(&self) -> Option<T> {
+        ArrayQueue::pop(self)
+    }
+
+    fn len(&self) -> usize {
+        ArrayQueue::len(self)
+    }
+
+    fn capacity(&self) -> usize {
+        ArrayQueue::capacity(self)
+    }
+}
+
+/// An unbounded queue; the pool never rejects a spawn.
+impl<T> TaskQueue<T> for SegQueue<T> {
+    fn push(&self, value: T) -> Result<(), T> {
+        SegQueue::push(self, value);
+        Ok(())
+    }
+
+    fn pop(&self) -> Option<T> {
+        SegQueue::pop(self)
+    }
+
+    fn len(&self) -> usize {
+        SegQueue::len(self)
+    }
+
+    fn capacity(&self) -> usize {
+        usize::MAX
+    }
+}
+
+/// The default queue of a busy pool: either a fixed ring buffer, or an unbounded queue
+/// whose capacity doubles on demand up to a maximum.
+///
+/// The growable capacity is bookkeeping only and publishes no data, so it is `Relaxed`.
+#[derive(Debug)]
+pub struct BusyQueue<T>(Inner<T>);
+
+#[derive(Debug)]
+enum Inner<T> {
+    Fixed(ArrayQueue<T>),
+    Growable {
+        queue: SegQueue<T>,
+        capacity: AtomicUsize,
+        max: usize,
+    },
+}
+
+impl<T> BusyQueue<T> {
+    pub(crate) fn fixed(cap: usize) -> Self {
+        Self(Inner::Fixed(ArrayQueue::new(cap)))
+    }
+
+    pub(crate) fn growable(initial: usize, max: usize) -> Self {
+        assert!(initial > 0 && initial <= max, "capacity must be non-zero and at most the max");
+        Self(Inner::Growable {
+            queue: SegQueue::new(),
+            capacity: AtomicUsize::new(initial),
+            max,
+        })
+    }
+}
+
+impl<T> TaskQueue<T> for BusyQueue<T> {
+    /// Pushes `value`, handing it back if a fixed queue is full.
+    ///
+    /// A growable queue never refuses a value; callers keep within
+    /// [`capacity`](TaskQueue::capacity) themselves.
+    fn push(&self, value: T) -> Result<(), T> {
+        match &self.0 {
+            Inner::Fixed(queue) => queue.push(value),
+            Inner::Growable { queue, .. } => {
+                queue.push(value);
+                Ok(())
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<T> {
+        match &self.0 {
+            Inner::Fixed(queue) => queue.pop(),
+            Inner::Growable { queue, .. } => queue.pop(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match &self.0 {
+            Inner::Fixed(queue) => queue.len(),
+            Inner::Growable { queue, .. } => queue.len(),
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        match &self.0 {
+            Inner::Fixed(queue) => queue.capacity(),
+            Inner::Growable { capacity, .. } => capacity.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Doubles the capacity, clamped to the max. Returns `false` if it cannot grow.
+    fn grow(&self) -> bool {
+        match &self.0 {
+            Inner::Fixed(_) => false,
+            Inner::Growable { capacity, max, .. } => capacity
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |cap| {
+                    (cap < *max).then(|| cap.saturating_mul(2).min(*max))
+                })
+                .is_ok(),
+        }
+    }
+}
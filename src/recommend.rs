@@ -0,0 +1,49 @@
+/// The pool implementations shipped by this crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolKind {
+    /// [`BusyLocalPool`](crate::BusyLocalPool): a fixed-capacity queue that polls every task each sweep.
+    Busy,
+    /// [`NewLocalPool`](crate::NewLocalPool): a growable `FuturesUnordered` that only re-polls woken tasks.
+    New,
+}
+
+/// Tasks above which the busy pool stops being the better choice, since it polls
+/// every queued task on each sweep while `FuturesUnordered` only polls woken ones.
+///
+/// A judgment call rather than a measured crossover: it keeps a sweep over idle tasks
+/// to roughly 11 µs in the `one_among_idle` benchmark below.
+const BUSY_MAX_TASKS: usize = 256;
+
+/// Suggest which pool to use for a workload.
+///
+/// Based on `benches/bench_local_pool.rs`, run with
+/// `cargo bench -- --warm-up-time 1 --measurement-time 3` on a single-core Intel Xeon
+/// VM with rustc 1.95. `spawn_many` spawns ready tasks and runs them to completion:
+///
+/// | tasks | `LocalPool` | `NewLocalPool` | `BusyLocalPool` |
+/// |------:|------------:|---------------:|----------------:|
+/// |     2 |      599 ns |         464 ns |          375 ns |
+/// |    20 |     3.80 µs |        3.67 µs |         2.82 µs |
+/// |   200 |     37.0 µs |        35.1 µs |         24.6 µs |
+///
+/// `one_among_idle` runs one ready task next to tasks that wait without being woken:
+///
+/// | idle tasks | `NewLocalPool` | `BusyLocalPool` |
+/// |-----------:|---------------:|----------------:|
+/// |         16 |         203 ns |          849 ns |
+/// |         64 |         209 ns |         2.94 µs |
+/// |        256 |         228 ns |         11.3 µs |
+/// |       1024 |         273 ns |         49.1 µs |
+///
+/// The busy pool is about 1.4 times as fast at spawning and completing tasks, saving
+/// some 50 ns per task, but every sweep costs it about 45 ns per idle task. It also
+/// needs its capacity up front, so it is only recommended for bounded workloads of
+/// moderate size. Everything else goes to the `FuturesUnordered` based pool, which is
+/// slightly faster than the channel based `LocalPool`.
+pub fn recommended_pool(expected_tasks: usize, bounded: bool) -> PoolKind {
+    if bounded && expected_tasks <= BUSY_MAX_TASKS {
+        PoolKind::Busy
+    } else {
+        PoolKind::New
+    }
+}
@@ -0,0 +1,65 @@
+use alloc::vec::Vec;
+use core::future::Future;
+use alloc::boxed::Box;
+use futures::task::UnsafeFutureObj;
+use crate::local_pool_new::LocalPool;
+
+/// A handle for spawning futures that borrow from the enclosing stack frame.
+///
+/// Created by [`scope`]. Every future spawned through it is driven to completion
+/// before [`scope`] returns, so data borrowed for `'a` is never freed while a task
+/// still refers to it.
+#[derive(Debug)]
+pub struct Scope<'a, Ret> {
+    pool: LocalPool<'a, Ret>,
+}
+
+impl<'a, Ret> Scope<'a, Ret> {
+    /// Spawn a future that may borrow anything living at least as long as the scope.
+    pub fn spawn_borrowed<F>(&mut self, f: F)
+        where F: Future<Output = Ret> + 'a {
+        self.pool.spawn(Box::pin(f))
+    }
+
+    /// Like [`spawn_borrowed`](Scope::spawn_borrowed), but takes any future object,
+    /// so a future pinned on the stack outside the scope can be spawned without boxing.
+    pub fn spawn<F>(&mut self, f: F)
+        where F: UnsafeFutureObj<'a, Ret> {
+        self.pool.spawn(f)
+    }
+}
+
+/// Runs `f` with a [`Scope`], then drives every future spawned on it to completion.
+///
+/// Returns the closure's value together with the results of all scoped tasks. If `f`
+/// or a task panics, the tasks left are dropped without being polled again before the
+/// panic leaves `scope`, so no task outlives the borrows it holds either way.
+///
+/// ```rust
+/// use minimal_executor::scope;
+///
+/// let data = vec![1, 2, 3];
+/// let ((), results) = scope(|s| {
+///     s.spawn_borrowed(async { data.iter().sum::<i32>() });
+///     s.spawn_borrowed(async { data.len() as i32 });
+/// });
+/// assert_eq!(results.iter().sum::<i32>(), 9);
+/// ```
+///
+/// A future may not borrow data that is dropped before the scope ends:
+///
+/// ```compile_fail
+/// use minimal_executor::scope;
+///
+/// scope(|s| {
+///     let data = vec![1, 2, 3];
+///     s.spawn_borrowed(async { data.len() });
+/// });
+/// ```
+pub fn scope<'a, Ret, T, F>(f: F) -> (T, Vec<Ret>)
+    where F: FnOnce(&mut Scope<'a, Ret>) -> T {
+    let mut scope = Scope { pool: LocalPool::new() };
+    let value = f(&mut scope);
+    let results = scope.pool.run();
+    (value, results)
+}
@@ -0,0 +1,68 @@
+use alloc::vec::Vec;
+
+/// Identifies a task spawned with an id, see
+/// [`spawn_with_id`](crate::BusyLocalPool::spawn_with_id) and
+/// [`spawn_deadline`](crate::LocalPool::spawn_deadline).
+///
+/// Ids are recycled once their task finishes; the generation tells a recycled id
+/// apart from the one it replaced. Generations are 64 bits wide, so an id is never
+/// handed out twice over the lifetime of a pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TaskId {
+    index: usize,
+    generation: u64,
+}
+
+#[derive(Debug)]
+struct Slot {
+    generation: u64,
+    live: bool,
+}
+
+/// A free-list of reusable id slots, so the table stays as large as the most ids ever
+/// live at once rather than growing with every spawn.
+#[derive(Debug, Default)]
+pub(crate) struct Slab {
+    slots: Vec<Slot>,
+    free: Vec<usize>,
+}
+
+impl Slab {
+    pub fn insert(&mut self) -> TaskId {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot { generation: 0, live: false });
+                self.slots.len() - 1
+            }
+        };
+        let slot = &mut self.slots[index];
+        slot.live = true;
+        TaskId { index, generation: slot.generation }
+    }
+
+    pub fn remove(&mut self, id: TaskId) {
+        if self.contains(id) {
+            let slot = &mut self.slots[id.index];
+            slot.live = false;
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free.push(id.index);
+        }
+    }
+
+    pub fn contains(&self, id: TaskId) -> bool {
+        self.slots.get(id.index).is_some_and(|slot| slot.live && slot.generation == id.generation)
+    }
+
+    /// The ids currently in use, in slot order.
+    pub fn live(&self) -> impl Iterator<Item = TaskId> + '_ {
+        self.slots.iter().enumerate()
+            .filter(|(_, slot)| slot.live)
+            .map(|(index, slot)| TaskId { index, generation: slot.generation })
+    }
+
+    /// Number of slots allocated, live or free.
+    pub fn slots(&self) -> usize {
+        self.slots.len()
+    }
+}
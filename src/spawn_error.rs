@@ -0,0 +1,56 @@
+use futures::task::SpawnError;
+
+/// Why a spawner turned a task away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnErrorKind {
+    /// The pool is gone, shut down or draining, so retrying cannot succeed.
+    Shutdown,
+    /// The pool is at capacity; retrying once tasks have finished may succeed.
+    Full,
+}
+
+/// The error `try_spawn` and the busy pool's own spawn methods return, handing back
+/// the future they could not spawn.
+///
+/// Lets callers back off and resubmit the same future, e.g. when a busy pool's queue
+/// is full.
+pub struct TrySpawnError<F> {
+    pub kind: SpawnErrorKind,
+    pub future: F,
+}
+
+impl<F> TrySpawnError<F> {
+    pub(crate) fn new(kind: SpawnErrorKind, future: F) -> Self {
+        Self { kind, future }
+    }
+
+    /// Takes the future back, dropping the error.
+    pub fn into_future(self) -> F {
+        self.future
+    }
+}
+
+impl<F> From<TrySpawnError<F>> for SpawnError {
+    fn from(_: TrySpawnError<F>) -> Self {
+        SpawnError::shutdown()
+    }
+}
+
+/// Futures rarely implement `Debug`, so this leaves the future out.
+impl<F> core::fmt::Debug for TrySpawnError<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TrySpawnError").field("kind", &self.kind).finish_non_exhaustive()
+    }
+}
+
+impl<F> core::fmt::Display for TrySpawnError<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.kind {
+            SpawnErrorKind::Shutdown => write!(f, "pool is shut down"),
+            SpawnErrorKind::Full => write!(f, "pool is full"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<F> std::error::Error for TrySpawnError<F> {}
@@ -0,0 +1,45 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use crate::slab::TaskId;
+
+/// One scheduling decision of a pool, recorded with the `trace-events` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Spawned(TaskId),
+    PollStart(TaskId),
+    /// The poll finished; `true` if the task returned `Ready`.
+    PollEnd(TaskId, bool),
+    Completed(TaskId),
+}
+
+/// A ring buffer of the most recent events.
+#[derive(Debug)]
+pub(crate) struct EventLog {
+    events: VecDeque<Event>,
+}
+
+impl EventLog {
+    /// Events kept before the oldest ones are overwritten.
+    pub const CAPACITY: usize = 1024;
+
+    pub fn push(&mut self, event: Event) {
+        if self.events.len() == Self::CAPACITY {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    pub fn snapshot(&self) -> Vec<Event> {
+        self.events.iter().copied().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self { events: VecDeque::with_capacity(Self::CAPACITY) }
+    }
+}
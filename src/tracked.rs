@@ -0,0 +1,116 @@
+use alloc::sync::Arc;
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::task::{Context, Poll};
+use futures::task::AtomicWaker;
+
+std::thread_local! {
+    /// The group of the tracked task being polled on this thread, if any.
+    static CURRENT: RefCell<Option<Arc<Group>>> = const { RefCell::new(None) };
+}
+
+/// Counts the unfinished tasks of one tracked subtree.
+#[derive(Debug)]
+pub(crate) struct Group {
+    pending: AtomicUsize,
+    waker: AtomicWaker,
+    /// The pool the subtree runs on; spawns into other pools do not join the group.
+    pool: usize,
+}
+
+impl Group {
+    fn finish(&self) {
+        if self.pending.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.waker.wake();
+        }
+    }
+}
+
+/// A task that belongs to a group, releasing its slot when it completes or is dropped.
+#[derive(Debug)]
+pub(crate) struct Tracked<F> {
+    group: Option<Arc<Group>>,
+    future: F,
+}
+
+impl<F: Future + Unpin> Future for Tracked<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let this = &mut *self;
+        // spawns made while polling the task join its group
+        let _restore = Restore(CURRENT.with(|current| current.replace(this.group.clone())));
+        let ret = Pin::new(&mut this.future).poll(cx);
+        if ret.is_ready() {
+            if let Some(group) = this.group.take() {
+                group.finish();
+            }
+        }
+        ret
+    }
+}
+
+/// Puts back the group that was current before a tracked task was polled, even if
+/// the poll panics.
+struct Restore(Option<Arc<Group>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let outer = self.0.take();
+        CURRENT.with(|current| current.replace(outer));
+    }
+}
+
+impl<F> Drop for Tracked<F> {
+    fn drop(&mut self) {
+        if let Some(group) = self.group.take() {
+            group.finish();
+        }
+    }
+}
+
+/// Identifies a pool for task tracking by the `closed` flag it shares with its spawners.
+pub(crate) fn pool_key(closed: &Arc<AtomicBool>) -> usize {
+    Arc::as_ptr(closed) as usize
+}
+
+/// Starts a new group with `future` as its root, on the pool identified by `pool`.
+pub(crate) fn track_root<F>(future: F, pool: usize) -> (Tracked<F>, CompletionHandle) {
+    let group = Arc::new(Group { pending: AtomicUsize::new(1), waker: AtomicWaker::new(), pool });
+    (Tracked { group: Some(group.clone()), future }, CompletionHandle { group })
+}
+
+/// Adds `future`, spawned into the pool identified by `pool`, to the group of the
+/// tracked task currently being polled, if that task runs on the same pool.
+pub(crate) fn track_current<F>(future: F, pool: usize) -> Result<Tracked<F>, F> {
+    match CURRENT.with(|current| current.borrow().clone()) {
+        Some(group) if group.pool == pool => {
+            group.pending.fetch_add(1, Ordering::Relaxed);
+            Ok(Tracked { group: Some(group), future })
+        }
+        _ => Err(future),
+    }
+}
+
+/// Resolves once a task spawned with
+/// [`spawn_tracked`](crate::LocalPool::spawn_tracked) and every task it spawned,
+/// directly or transitively, have completed.
+#[derive(Debug)]
+pub struct CompletionHandle {
+    group: Arc<Group>,
+}
+
+impl Future for CompletionHandle {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        self.group.waker.register(cx.waker());
+        if self.group.pending.load(Ordering::Acquire) == 0 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
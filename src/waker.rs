@@ -1,15 +1,16 @@
 use core::mem::ManuallyDrop;
-use core::sync::atomic::{AtomicBool, Ordering};
-use futures::task::WakerRef;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use core::task::{Waker, RawWaker, RawWakerVTable};
+use futures::task::{ArcWake, AtomicWaker};
+use alloc::sync::Arc;
+use alloc::rc::Rc;
 
-#[allow(dead_code)]
-#[derive(Debug)]
+/// A wake flag: waking sets it and [`read_reset`](SingleWake::read_reset) takes it.
+#[derive(Debug, Default)]
 pub struct SingleWake {
     woken: AtomicBool,
 }
 
-#[allow(dead_code)]
 impl SingleWake {
     pub fn new() -> Self {
         Self {
@@ -27,6 +28,8 @@ impl SimpleWaker for SingleWake {
     }
 }
 
+/// A waker that does nothing, for callers that re-poll regardless of wake-ups.
+#[derive(Debug, Default)]
 pub struct AlwaysWake {}
 
 impl AlwaysWake {
@@ -40,52 +43,292 @@ impl SimpleWaker for AlwaysWake {
     fn wake(&self) {}
 }
 
-pub(super) trait SimpleWaker {
-    fn wake(&self);
+static ALWAYS_WAKER: Waker = unsafe {
+    Waker::from_raw(RawWaker::new(
+        &AlwaysWake::INSTANCE as *const AlwaysWake as *const (),
+        waker_vtable::<AlwaysWake>(),
+    ))
+};
+
+/// The [`AlwaysWake`] waker, built once so hot loops don't rebuild it for every poll.
+pub(crate) fn always_waker() -> &'static Waker {
+    &ALWAYS_WAKER
 }
 
-/// Creates a reference to a [`Waker`] from a reference to `Arc<impl ArcWake>`.
+/// A [`Waker`] that does nothing when woken.
+pub fn noop_waker() -> Waker {
+    always_waker().clone()
+}
+
+/// A waker that counts how often it was woken.
+///
+/// ```rust
+/// use core::future::Future;
+/// use core::task::{Context, Poll};
+/// use minimal_executor::waker::CountingWaker;
 ///
-/// The resulting [`Waker`] will call
-/// [`ArcWake.wake()`](ArcWake::wake) if awoken.
-#[inline]
-pub(crate) fn waker_ref<W>(wake: &W) -> WakerRef<'_>
-    where
-        W: SimpleWaker,
-{
-    let ptr = (wake as *const W) as *const ();
+/// let counter = CountingWaker::new();
+/// let waker = counter.waker();
+/// let mut cx = Context::from_waker(&waker);
+/// let mut fut = Box::pin(futures::future::poll_fn(|cx| {
+///     cx.waker().wake_by_ref();
+///     cx.waker().clone().wake();
+///     Poll::<()>::Pending
+/// }));
+/// assert!(fut.as_mut().poll(&mut cx).is_pending());
+/// assert_eq!(counter.count(), 2);
+/// ```
+#[derive(Debug, Default)]
+pub struct CountingWaker {
+    wakes: AtomicUsize,
+}
+
+impl CountingWaker {
+    pub fn new() -> Arc<Self> {
+        Arc::default()
+    }
+    /// A [`Waker`] that increments this counter, by value or by reference.
+    pub fn waker(self: &Arc<Self>) -> Waker {
+        futures::task::waker(self.clone())
+    }
+    /// How many times a waker from [`waker`](CountingWaker::waker) has been woken.
+    pub fn count(&self) -> usize {
+        self.wakes.load(Ordering::Relaxed)
+    }
+}
+
+impl ArcWake for CountingWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.wakes.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// How a pool wakes the tasks it polls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WakerStrategy {
+    /// Every task is polled on every sweep, whether it asked to be woken or not.
+    /// Best for CPU-bound tasks that are almost always ready.
+    #[default]
+    Busy,
+    /// Each task gets its own wake flag and is only re-polled after it has been woken.
+    SingleFlag,
+    /// Like [`SingleFlag`](WakerStrategy::SingleFlag), but `run` parks the thread when
+    /// no task has been woken, and a wake unparks it again.
+    #[cfg(feature = "std")]
+    Parking,
+}
 
-    let waker =
-        ManuallyDrop::new(unsafe { Waker::from_raw(RawWaker::new(ptr, waker_vtable::<W>())) });
-    WakerRef::new_unowned(waker)
+/// The per-task waker handed out by pools that don't use [`WakerStrategy::Busy`].
+#[derive(Debug)]
+pub(crate) struct TaskWake {
+    woken: SingleWake,
+    /// The waker of the context the pool was last polled with, so wake-ups also reach
+    /// an outer executor driving the pool.
+    outer: AtomicWaker,
+    #[cfg(feature = "std")]
+    thread: Option<std::thread::Thread>,
+}
+
+impl TaskWake {
+    /// Tasks start out woken so that they get their first poll.
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    pub fn new(strategy: WakerStrategy) -> Arc<Self> {
+        let woken = SingleWake::new();
+        woken.wake();
+        Arc::new(Self {
+            woken,
+            outer: AtomicWaker::new(),
+            #[cfg(feature = "std")]
+            thread: match strategy {
+                WakerStrategy::Parking => Some(std::thread::current()),
+                _ => None,
+            },
+        })
+    }
+    pub fn read_reset(&self) -> bool {
+        self.woken.read_reset()
+    }
+    /// Forwards later wake-ups to `outer` as well.
+    pub fn register(&self, outer: &Waker) {
+        self.outer.register(outer);
+    }
+}
+
+impl ArcWake for TaskWake {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.woken.wake();
+        arc_self.outer.wake();
+        #[cfg(feature = "std")]
+        if let Some(thread) = &arc_self.thread {
+            thread.unpark();
+        }
+    }
+}
+
+/// A waker that flags its task as woken and unparks the thread that created it.
+///
+/// The flag is set before unparking, so a wake that lands between a poll and the
+/// following park is never lost: either the flag is seen before parking, or the
+/// unpark token makes the park return straight away.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub(crate) struct ParkWaker {
+    woken: SingleWake,
+    thread: std::thread::Thread,
+}
+
+#[cfg(feature = "std")]
+impl ParkWaker {
+    /// Starts out woken so that the future gets its first poll.
+    pub fn new() -> Arc<Self> {
+        let woken = SingleWake::new();
+        woken.wake();
+        Arc::new(Self { woken, thread: std::thread::current() })
+    }
+    pub fn read_reset(&self) -> bool {
+        self.woken.read_reset()
+    }
+}
+
+#[cfg(feature = "std")]
+impl ArcWake for ParkWaker {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.woken.wake();
+        arc_self.thread.unpark();
+    }
+}
+
+/// Something a [`Waker`] can signal, e.g. a flag an interrupt handler or reactor
+/// checks.
+///
+/// Turn it into a waker with [`waker_from_arc`].
+pub trait SimpleWaker {
+    fn wake(&self);
 }
 
-pub(super) fn waker_vtable<W: SimpleWaker>() -> &'static RawWakerVTable {
+/// The vtable of a waker that borrows its `SimpleWaker` for the whole program, like
+/// [`always_waker`]: cloning copies the pointer and dropping, including the drop after
+/// a by-value [`Waker::wake`], does nothing.
+const fn waker_vtable<W: SimpleWaker>() -> &'static RawWakerVTable {
     &RawWakerVTable::new(
         copy_ref_raw::<W>,
-        wake_raw::<W>,
         wake_by_ref_raw::<W>,
-        drop_raw::<W>,
+        wake_by_ref_raw::<W>,
+        forget_ref_raw,
     )
 }
 
-
 unsafe fn copy_ref_raw<T: SimpleWaker>(data: *const ()) -> RawWaker {
     RawWaker::new(data, waker_vtable::<T>())
 }
 
-unsafe fn wake_raw<T: SimpleWaker>(data: *const ()) {
-    let data = core::ptr::read(data as *const T);
-    SimpleWaker::wake(&data);
-    drop(data)
-}
-
 unsafe fn wake_by_ref_raw<T: SimpleWaker>(data: *const ()) {
     let data = &*(data as *const T);
     SimpleWaker::wake(data);
 }
 
-unsafe fn drop_raw<T: SimpleWaker>(data: *const ()) {
-    let data = core::ptr::read(data as *const T);
-    drop(data)
+unsafe fn forget_ref_raw(_data: *const ()) {}
+
+/// Creates a [`Waker`] that owns a reference count of `wake`.
+///
+/// Cloning increments the count, and dropping or waking by value decrements it again,
+/// so the waker and its clones may outlive every other handle to `wake`.
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use minimal_executor::waker::{waker_from_arc, SimpleWaker};
+///
+/// struct Flag(AtomicBool);
+///
+/// impl SimpleWaker for Flag {
+///     fn wake(&self) {
+///         self.0.store(true, Ordering::Release);
+///     }
+/// }
+///
+/// let flag = Arc::new(Flag(AtomicBool::new(false)));
+/// let waker = waker_from_arc(flag.clone());
+/// waker.clone().wake();
+/// assert!(flag.0.load(Ordering::Acquire));
+/// drop(waker);
+/// assert_eq!(Arc::strong_count(&flag), 1);
+/// ```
+pub fn waker_from_arc<W: SimpleWaker + Send + Sync + 'static>(wake: Arc<W>) -> Waker {
+    let ptr = Arc::into_raw(wake) as *const ();
+    unsafe { Waker::from_raw(RawWaker::new(ptr, arc_waker_vtable::<W>())) }
+}
+
+fn arc_waker_vtable<W: SimpleWaker + Send + Sync + 'static>() -> &'static RawWakerVTable {
+    &RawWakerVTable::new(
+        clone_arc_raw::<W>,
+        wake_arc_raw::<W>,
+        wake_by_ref_raw::<W>,
+        drop_arc_raw::<W>,
+    )
+}
+
+unsafe fn clone_arc_raw<W: SimpleWaker + Send + Sync + 'static>(data: *const ()) -> RawWaker {
+    Arc::increment_strong_count(data as *const W);
+    RawWaker::new(data, arc_waker_vtable::<W>())
+}
+
+unsafe fn wake_arc_raw<W: SimpleWaker + Send + Sync + 'static>(data: *const ()) {
+    let wake = Arc::from_raw(data as *const W);
+    SimpleWaker::wake(&*wake);
+}
+
+unsafe fn drop_arc_raw<W: SimpleWaker + Send + Sync + 'static>(data: *const ()) {
+    drop(Arc::from_raw(data as *const W));
+}
+
+/// A waker that lives on a single thread, reference counted with `Rc` instead of `Arc`.
+///
+/// Useful for `!Send` tasks in single-threaded pools, where the atomic refcount of an
+/// `Arc` based waker is pure overhead.
+pub trait LocalWake {
+    fn wake_by_ref(self: &Rc<Self>);
+
+    fn wake(self: Rc<Self>) {
+        self.wake_by_ref()
+    }
+}
+
+/// Creates a [`Waker`] backed by an `Rc<W>`.
+///
+/// # Safety
+///
+/// `Waker` is `Send + Sync`, but the returned one is not: it, and every clone of it,
+/// must be used and dropped on the thread that created it. Only hand it to futures
+/// known not to move their waker to another thread.
+pub unsafe fn local_waker<W: LocalWake + 'static>(wake: Rc<W>) -> Waker {
+    let ptr = Rc::into_raw(wake) as *const ();
+    Waker::from_raw(RawWaker::new(ptr, local_waker_vtable::<W>()))
+}
+
+fn local_waker_vtable<W: LocalWake + 'static>() -> &'static RawWakerVTable {
+    &RawWakerVTable::new(
+        clone_rc_raw::<W>,
+        wake_rc_raw::<W>,
+        wake_by_ref_rc_raw::<W>,
+        drop_rc_raw::<W>,
+    )
+}
+
+unsafe fn clone_rc_raw<W: LocalWake + 'static>(data: *const ()) -> RawWaker {
+    Rc::increment_strong_count(data as *const W);
+    RawWaker::new(data, local_waker_vtable::<W>())
+}
+
+unsafe fn wake_rc_raw<W: LocalWake + 'static>(data: *const ()) {
+    LocalWake::wake(Rc::from_raw(data as *const W));
+}
+
+unsafe fn wake_by_ref_rc_raw<W: LocalWake + 'static>(data: *const ()) {
+    let wake = ManuallyDrop::new(Rc::from_raw(data as *const W));
+    LocalWake::wake_by_ref(&*wake);
+}
+
+unsafe fn drop_rc_raw<W: LocalWake + 'static>(data: *const ()) {
+    drop(Rc::from_raw(data as *const W));
 }
@@ -0,0 +1,85 @@
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::{Cell, RefCell};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::Context;
+use crate::waker::{local_waker, LocalWake};
+
+/// Drives `pool` from a host event loop, e.g. a browser's, instead of blocking.
+///
+/// `schedule` is handed a callback whenever the pool needs to be polled again: once
+/// straight away, then on every wake-up from one of its tasks. Each callback advances
+/// the pool as [`poll_step`](crate::NewLocalPool::poll_step) would, and the pool is
+/// dropped once it is empty. Wake-ups that arrive while a callback is already queued
+/// are merged into it. Results are discarded; feed more tasks in through a spawner
+/// taken before handing the pool over.
+///
+/// `schedule` must defer the callback, e.g. with `queueMicrotask` or
+/// `requestAnimationFrame`; running it straight away panics, since the pool is
+/// borrowed while it is polled.
+///
+/// # Safety
+///
+/// The wakers handed to the tasks are not thread-safe, so no task may send one to
+/// another thread. This always holds on `wasm32` without the `atomics` target feature.
+///
+/// ```rust
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+/// use minimal_executor::{spawn_on_microtask, NewLocalPool};
+///
+/// let queue = Rc::new(RefCell::new(Vec::<Box<dyn FnOnce()>>::new()));
+/// let mut pool = NewLocalPool::new();
+/// pool.spawn(Box::pin(async {}));
+/// let microtasks = queue.clone();
+/// unsafe { spawn_on_microtask(pool, move |task| microtasks.borrow_mut().push(task)) };
+///
+/// // stand-in for the browser running its microtask queue
+/// while let Some(task) = queue.borrow_mut().pop() {
+///     task();
+/// }
+/// ```
+pub unsafe fn spawn_on_microtask<P, S>(pool: P, schedule: S)
+    where P: 'static, for<'x> &'x mut P: Future, S: Fn(Box<dyn FnOnce()>) + 'static {
+    let driver = Rc::new(Microtask {
+        pool: RefCell::new(Some(pool)),
+        schedule,
+        queued: Cell::new(false),
+    });
+    driver.wake_by_ref();
+}
+
+struct Microtask<P, S> {
+    pool: RefCell<Option<P>>,
+    schedule: S,
+    /// Whether a callback is already waiting to poll the pool.
+    queued: Cell<bool>,
+}
+
+impl<P, S> Microtask<P, S>
+    where P: 'static, for<'x> &'x mut P: Future, S: Fn(Box<dyn FnOnce()>) + 'static {
+    fn step(self: Rc<Self>) {
+        self.queued.set(false);
+        // SAFETY: upheld by the caller of `spawn_on_microtask`
+        let waker = unsafe { local_waker(self.clone()) };
+        let mut cx = Context::from_waker(&waker);
+        let mut slot = self.pool.borrow_mut();
+        if let Some(pool) = slot.as_mut() {
+            if Pin::new(&mut &mut *pool).poll(&mut cx).is_ready() {
+                *slot = None;
+            }
+        }
+    }
+}
+
+impl<P, S> LocalWake for Microtask<P, S>
+    where P: 'static, for<'x> &'x mut P: Future, S: Fn(Box<dyn FnOnce()>) + 'static {
+    fn wake_by_ref(self: &Rc<Self>) {
+        if self.queued.replace(true) {
+            return;
+        }
+        let this = self.clone();
+        (self.schedule)(Box::new(move || this.step()));
+    }
+}
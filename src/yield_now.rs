@@ -0,0 +1,44 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// Returns `Pending` once, waking the task straight away, so the pool runs the other
+/// tasks before this one continues.
+///
+/// Insert it between long synchronous sections of a task to keep it from holding up
+/// the rest of the pool. The wake sets the task's flag under
+/// [`WakerStrategy::SingleFlag`](crate::WakerStrategy::SingleFlag), so the task is
+/// polled again on the next sweep rather than left waiting.
+///
+/// ```rust
+/// use minimal_executor::{block_on_profiled, yield_now};
+///
+/// let ((), polls) = block_on_profiled(async {
+///     yield_now().await;
+///     yield_now().await;
+/// });
+/// assert_eq!(polls, 3);
+/// ```
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+/// The future returned by [`yield_now`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
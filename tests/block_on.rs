@@ -0,0 +1,358 @@
+use minimal_executor::{block_hal, block_on_cooperative, block_on_idle, block_on_parked};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A mock async I2C read that needs a number of polls before the bus transfer finishes.
+struct MockI2cRead {
+    polls_until_ready: usize,
+    polls: usize,
+    data: u8,
+}
+
+impl Future for MockI2cRead {
+    type Output = Result<u8, ()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.polls += 1;
+        if self.polls >= self.polls_until_ready {
+            Poll::Ready(Ok(self.data))
+        } else {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+#[test]
+fn block_hal_drives_mock_peripheral() {
+    let mut read = MockI2cRead {
+        polls_until_ready: 4,
+        polls: 0,
+        data: 0x5a,
+    };
+    assert_eq!(block_hal(&mut read), Ok(0x5a));
+    assert_eq!(read.polls, 4);
+}
+
+/// Yields back to the executor a number of times before completing.
+struct Yielding {
+    remaining: usize,
+}
+
+impl Future for Yielding {
+    type Output = usize;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<usize> {
+        if self.remaining == 0 {
+            return Poll::Ready(42);
+        }
+        self.remaining -= 1;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[test]
+fn block_on_cooperative_completes_yielding_future() {
+    assert_eq!(block_on_cooperative(Yielding { remaining: 10 }), 42);
+}
+
+#[test]
+fn block_on_idle_sleeps_until_woken() {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+    use std::task::Waker;
+
+    let ready = Rc::new(Cell::new(false));
+    let registered: Rc<RefCell<Option<Waker>>> = Rc::default();
+    let mut idles = 0;
+
+    let (flag, slot) = (ready.clone(), registered.clone());
+    let transfer = futures::future::poll_fn(move |cx| {
+        if flag.get() {
+            Poll::Ready("done")
+        } else {
+            *slot.borrow_mut() = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    });
+
+    let ret = block_on_idle(transfer, || {
+        idles += 1;
+        if idles == 3 {
+            // simulate the interrupt handler completing the transfer
+            ready.set(true);
+            registered.borrow_mut().take().unwrap().wake();
+        }
+    });
+    assert_eq!(ret, "done");
+    assert_eq!(idles, 3);
+}
+
+#[test]
+fn poll_fn_woken_repolls_only_while_woken() {
+    use minimal_executor::poll_fn_woken_with;
+
+    let mut polls = 0;
+    let mut idles = 0;
+    let ret = poll_fn_woken_with(|cx| {
+        polls += 1;
+        if polls > 5 {
+            return Poll::Ready(polls);
+        }
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }, || idles += 1);
+    assert_eq!(ret, 6);
+    assert_eq!(idles, 0);
+}
+
+#[test]
+fn block_on_with_events_services_stream_until_main_completes() {
+    use minimal_executor::block_on_with_events;
+    use std::cell::Cell;
+
+    let sum = Cell::new(0);
+    let main = futures::future::poll_fn(|_| {
+        if sum.get() == 6 { Poll::Ready("main done") } else { Poll::Pending }
+    });
+    let events = futures::stream::iter(vec![1, 2, 3]);
+    assert_eq!(block_on_with_events(main, events, |item| sum.set(sum.get() + item)), "main done");
+    assert_eq!(sum.get(), 6);
+}
+
+#[test]
+fn block_on_profiled_counts_polls() {
+    use minimal_executor::block_on_profiled;
+
+    let read = MockI2cRead { polls_until_ready: 5, polls: 0, data: 0x42 };
+    assert_eq!(block_on_profiled(read), (Ok(0x42), 5));
+}
+
+#[test]
+fn block_on_instrumented_reports_first_poll_readiness() {
+    use minimal_executor::block_on_instrumented;
+
+    let read = MockI2cRead { polls_until_ready: 5, polls: 0, data: 0x42 };
+    assert_eq!(block_on_instrumented(read), (Ok(0x42), 5, false));
+    let read = MockI2cRead { polls_until_ready: 1, polls: 0, data: 0x7 };
+    assert_eq!(block_on_instrumented(read), (Ok(0x7), 1, true));
+}
+
+#[test]
+fn block_on_parked_polls_once_per_wake() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::task::Waker;
+
+    let registered: Arc<Mutex<Option<Waker>>> = Arc::default();
+    let wakes = Arc::new(AtomicUsize::new(0));
+    let (slot, sent) = (registered.clone(), wakes.clone());
+    let main = std::thread::current();
+    let waker_thread = std::thread::spawn(move || {
+        for _ in 0..3 {
+            let waker = loop {
+                if let Some(waker) = slot.lock().unwrap().take() {
+                    break waker;
+                }
+                std::thread::yield_now();
+            };
+            // spurious unparks must not cause extra polls
+            main.unpark();
+            sent.fetch_add(1, Ordering::SeqCst);
+            waker.wake();
+            main.unpark();
+        }
+    });
+
+    let mut polls = 0;
+    let ret = block_on_parked(futures::future::poll_fn(|cx| {
+        polls += 1;
+        if wakes.load(Ordering::SeqCst) == 3 {
+            return Poll::Ready(polls);
+        }
+        *registered.lock().unwrap() = Some(cx.waker().clone());
+        Poll::Pending
+    }));
+    waker_thread.join().unwrap();
+    assert_eq!(ret, 4);
+}
+
+#[test]
+fn block_fn_only_repolls_after_a_wake() {
+    use minimal_executor::{block_fn, block_fn_hot};
+    use std::time::Duration;
+
+    /// Hands the waker to a thread that wakes it once after a few spins' worth of delay.
+    fn delayed_wake(cx: &mut Context<'_>, polls: &mut usize) -> Poll<()> {
+        *polls += 1;
+        if *polls > 1 {
+            return Poll::Ready(());
+        }
+        let waker = cx.waker().clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            waker.wake();
+        });
+        Poll::Pending
+    }
+
+    let mut polls = 0;
+    block_fn(|cx| delayed_wake(cx, &mut polls));
+    assert_eq!(polls, 2);
+
+    // the hot variant keeps polling a closure that never arranges a wake-up
+    let mut hot_polls = 0;
+    block_fn_hot(|_| {
+        hot_polls += 1;
+        if hot_polls < 100 { Poll::Pending } else { Poll::Ready(()) }
+    });
+    assert_eq!(hot_polls, 100);
+}
+
+#[test]
+fn block_on_timeout_counts_completion_on_last_poll_as_success() {
+    use minimal_executor::{block_on_timeout, Timeout};
+    use std::time::Duration;
+
+    /// Sleeps past the deadline inside its final poll, then completes.
+    struct SlowLastPoll(usize);
+
+    impl Future for SlowLastPoll {
+        type Output = &'static str;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<&'static str> {
+            self.0 -= 1;
+            if self.0 == 0 {
+                std::thread::sleep(Duration::from_millis(30));
+                return Poll::Ready("done");
+            }
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    assert_eq!(block_on_timeout(SlowLastPoll(3), Duration::from_millis(10)), Ok("done"));
+    assert_eq!(block_on_timeout(Yielding { remaining: 3 }, Duration::from_secs(1)), Ok(42));
+    let dropped = std::rc::Rc::new(());
+    let held = dropped.clone();
+    let hung = async move {
+        futures::future::pending::<()>().await;
+        drop(held);
+    };
+    assert_eq!(block_on_timeout(hung, Duration::from_millis(10)), Err(Timeout));
+    assert_eq!(std::rc::Rc::strong_count(&dropped), 1);
+}
+
+#[test]
+fn select_block_on_drops_the_loser_and_favors_a_on_ties() {
+    use futures::future::Either;
+    use minimal_executor::select_block_on;
+    use std::rc::Rc;
+
+    let loser = Rc::new(());
+    let held = loser.clone();
+    let slow = async move {
+        futures::future::pending::<()>().await;
+        drop(held);
+    };
+    assert!(matches!(select_block_on(slow, Yielding { remaining: 3 }), Either::Right(42)));
+    assert_eq!(Rc::strong_count(&loser), 1);
+
+    assert!(matches!(select_block_on(async { "a" }, async { "b" }), Either::Left("a")));
+}
+
+#[test]
+fn join_block_on_keeps_the_first_result_until_the_second_is_ready() {
+    use minimal_executor::join_block_on;
+    use std::cell::Cell;
+
+    let a_polls = Cell::new(0);
+    let a = futures::future::poll_fn(|_| {
+        a_polls.set(a_polls.get() + 1);
+        Poll::Ready("a")
+    });
+    let b = MockI2cRead { polls_until_ready: 5, polls: 0, data: 0x17 };
+    assert_eq!(join_block_on(a, b), ("a", Ok(0x17)));
+    // a finished on its first poll and was not polled again
+    assert_eq!(a_polls.get(), 1);
+}
+
+#[test]
+fn block_on_budget_counts_polls() {
+    use minimal_executor::{block_on_budget, BudgetExceeded};
+
+    let read = MockI2cRead { polls_until_ready: 5, polls: 0, data: 0x42 };
+    assert_eq!(block_on_budget(read, 5), Ok(Ok(0x42)));
+    let read = MockI2cRead { polls_until_ready: 5, polls: 0, data: 0x42 };
+    assert_eq!(block_on_budget(read, 4), Err(BudgetExceeded { polls: 4 }));
+    assert_eq!(block_on_budget(async { 1 }, 0), Err(BudgetExceeded { polls: 0 }));
+}
+
+#[test]
+fn block_on_in_pool_keeps_pending_tasks() {
+    use minimal_executor::{block_on_in_pool, LocalPool};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let (tx, rx) = futures::channel::oneshot::channel::<u32>();
+    let (later_tx, later_rx) = futures::channel::oneshot::channel::<()>();
+    let finished = Rc::new(Cell::new(false));
+    let mut pool = LocalPool::new();
+    pool.spawn(Box::pin(async move { tx.send(9).unwrap() }));
+    let flag = finished.clone();
+    pool.spawn(Box::pin(async move {
+        later_rx.await.unwrap();
+        flag.set(true);
+    }));
+
+    assert_eq!(block_on_in_pool(&mut pool, rx), Ok(9));
+    assert_eq!(pool.len(), 1);
+    later_tx.send(()).unwrap();
+    pool.run();
+    assert!(finished.get());
+}
+
+#[test]
+fn block_fn_with_yields_while_waiting_for_another_thread() {
+    use minimal_executor::{block_fn_with, BlockConfig};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    let ready = Arc::new(AtomicBool::new(false));
+    let mut handle = None;
+    let config = BlockConfig { spin_hint: false, yield_every: Some(1) };
+    block_fn_with(config, |cx| {
+        if ready.load(Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+        if handle.is_none() {
+            let (ready, waker) = (ready.clone(), cx.waker().clone());
+            handle = Some(std::thread::spawn(move || {
+                ready.store(true, Ordering::Release);
+                waker.wake();
+            }));
+        }
+        Poll::Pending
+    });
+    assert!(ready.load(Ordering::Acquire));
+    handle.unwrap().join().unwrap();
+}
+
+#[test]
+fn poll_all_reports_each_future_in_order() {
+    use minimal_executor::poll_all;
+
+    let (tx, rx) = futures::channel::oneshot::channel::<u32>();
+    let mut futs: Vec<Pin<Box<dyn Future<Output = u32>>>> = vec![Box::pin(async { 1 }), Box::pin(async { rx.await.unwrap() })];
+    let states = {
+        let mut pins: Vec<_> = futs.iter_mut().map(|f| f.as_mut()).collect();
+        poll_all(&mut pins)
+    };
+    assert_eq!(states, vec![Poll::Ready(1), Poll::Pending]);
+
+    tx.send(2).unwrap();
+    let mut pins = [futs[1].as_mut()];
+    assert_eq!(poll_all(&mut pins), vec![Poll::Ready(2)]);
+}
@@ -0,0 +1,935 @@
+use minimal_executor::{BusyLocalPool, PollBudget, WakerStrategy};
+use futures::future::{poll_fn, FutureExt};
+use futures::task::Poll;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Counts its polls and never asks to be woken.
+fn idle_task(polls: Rc<Cell<usize>>) -> impl std::future::Future<Output = ()> {
+    poll_fn(move |_| {
+        polls.set(polls.get() + 1);
+        Poll::Pending
+    })
+}
+
+fn idle_polls(strategy: WakerStrategy) -> usize {
+    let polls = Rc::new(Cell::new(0));
+    let mut pool = BusyLocalPool::with_strategy(4, strategy);
+    pool.spawn(idle_task(polls.clone()).boxed_local()).unwrap();
+    for _ in 0..10 {
+        assert!(pool.poll_though().is_pending());
+    }
+    polls.get()
+}
+
+#[test]
+fn busy_strategy_polls_idle_task_every_sweep() {
+    assert_eq!(idle_polls(WakerStrategy::Busy), 10);
+}
+
+#[test]
+fn single_flag_strategy_skips_unwoken_task() {
+    assert_eq!(idle_polls(WakerStrategy::SingleFlag), 1);
+}
+
+#[test]
+fn single_flag_strategy_repolls_woken_task() {
+    let polls = Rc::new(Cell::new(0));
+    let mut pool = BusyLocalPool::with_strategy(4, WakerStrategy::SingleFlag);
+    let counter = polls.clone();
+    pool.spawn(poll_fn(move |cx| {
+        counter.set(counter.get() + 1);
+        if counter.get() == 3 {
+            Poll::Ready(())
+        } else {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }).boxed_local()).unwrap();
+    assert_eq!(pool.run().len(), 1);
+    assert_eq!(polls.get(), 3);
+}
+
+#[test]
+fn parking_strategy_is_unparked_by_wake() {
+    let mut pool = BusyLocalPool::with_strategy(4, WakerStrategy::Parking);
+    let mut started = false;
+    pool.spawn(poll_fn(move |cx| {
+        if started {
+            return Poll::Ready(());
+        }
+        started = true;
+        let waker = cx.waker().clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            waker.wake();
+        });
+        Poll::Pending
+    }).boxed_local()).unwrap();
+    assert_eq!(pool.run().len(), 1);
+}
+
+#[test]
+fn poll_budget_is_shared_across_pools() {
+    let polls = Rc::new(Cell::new(0));
+    let budget = PollBudget::new(5);
+    let mut first = BusyLocalPool::new(8);
+    let mut second = BusyLocalPool::new(8);
+    for _ in 0..4 {
+        first.spawn(idle_task(polls.clone()).boxed_local()).unwrap();
+        second.spawn(idle_task(polls.clone()).boxed_local()).unwrap();
+    }
+
+    assert!(first.poll_budgeted(&budget).is_pending());
+    assert!(second.poll_budgeted(&budget).is_pending());
+
+    assert_eq!(polls.get(), 5);
+    assert!(budget.is_exhausted());
+}
+
+#[test]
+fn display_reports_len_capacity_and_completed() {
+    let mut pool = BusyLocalPool::new(256);
+    for _ in 0..3 {
+        pool.spawn(futures::future::pending::<()>().boxed_local()).unwrap();
+    }
+    pool.spawn(futures::future::ready(()).boxed_local()).unwrap();
+    assert_eq!(pool.poll_once(), Poll::Ready(Some(())));
+    assert_eq!(pool.to_string(), "pool: 3/256 tasks, 1 completed");
+}
+
+#[test]
+fn spawn_priority_tasks_are_polled_before_spawned_ones() {
+    let order = Rc::new(std::cell::RefCell::new(Vec::new()));
+    let mut pool = BusyLocalPool::new(4);
+    let log = order.clone();
+    pool.spawn(futures::future::lazy(move |_| log.borrow_mut().push("spawn")).boxed_local()).unwrap();
+    let log = order.clone();
+    pool.spawn_priority(futures::future::lazy(move |_| log.borrow_mut().push("first")).boxed_local()).unwrap();
+
+    assert!(pool.poll_though().is_ready());
+    assert_eq!(*order.borrow(), vec!["first"]);
+    pool.run();
+    assert_eq!(*order.borrow(), vec!["first", "spawn"]);
+}
+
+#[test]
+fn pending_first_task_does_not_starve_others() {
+    let polls = Rc::new(Cell::new(0));
+    let mut pool = BusyLocalPool::new(4);
+    pool.spawn_priority(idle_task(polls.clone()).boxed_local()).unwrap();
+    pool.spawn(futures::future::ready(()).boxed_local()).unwrap();
+
+    assert_eq!(pool.poll_once(), std::task::Poll::Ready(Some(())));
+    assert_eq!(polls.get(), 1);
+}
+
+#[test]
+fn as_future_drives_pool_inside_another_executor() {
+    let cnt = Rc::new(Cell::new(0));
+    let mut pool = BusyLocalPool::new(8);
+    for _ in 0..5 {
+        let cnt = cnt.clone();
+        pool.spawn(futures::future::lazy(move |_| cnt.set(cnt.get() + 1)).boxed_local()).unwrap();
+    }
+    futures::executor::block_on(pool.as_future());
+    assert_eq!(cnt.get(), 5);
+}
+
+#[test]
+fn as_future_only_wakes_the_outer_executor_for_task_wakeups() {
+    use minimal_executor::waker::CountingWaker;
+    use minimal_executor::yield_now;
+    use std::future::Future;
+
+    let counter = CountingWaker::new();
+    let waker = counter.waker();
+    let mut cx = std::task::Context::from_waker(&waker);
+    let (tx, rx) = futures::channel::oneshot::channel::<()>();
+    let mut pool = BusyLocalPool::with_strategy(4, WakerStrategy::SingleFlag);
+    pool.spawn(async move { rx.await.unwrap() }.boxed_local()).unwrap();
+    {
+        let mut fut = std::pin::pin!(pool.as_future());
+        assert!(fut.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(counter.count(), 0);
+        tx.send(()).unwrap();
+        assert_eq!(counter.count(), 1);
+        assert!(fut.as_mut().poll(&mut cx).is_ready());
+    }
+    pool.spawn(async { yield_now().await }.boxed_local()).unwrap();
+    futures::executor::block_on(pool.as_future());
+    assert!(pool.is_empty());
+}
+
+#[test]
+fn as_task_nests_one_pool_in_another() {
+    let (tx, rx) = futures::channel::oneshot::channel();
+    let mut inner = BusyLocalPool::new(4);
+    inner.spawn(async { 1 }.boxed_local()).unwrap();
+    inner.spawn(async move { rx.await.unwrap() }.boxed_local()).unwrap();
+
+    let mut outer = BusyLocalPool::new(4);
+    outer.spawn(inner.as_task().map(|results| results.iter().sum::<usize>()).boxed_local()).unwrap();
+    outer.spawn(async move {
+        tx.send(2).unwrap();
+        0
+    }.boxed_local()).unwrap();
+    let mut results = outer.run();
+    results.sort();
+    assert_eq!(results, vec![0, 3]);
+    drop(outer);
+    assert!(inner.is_empty());
+}
+
+#[test]
+fn as_task_wakes_a_wake_driven_outer_executor() {
+    use minimal_executor::yield_now;
+    use std::time::Duration;
+
+    for strategy in [WakerStrategy::Busy, WakerStrategy::SingleFlag, WakerStrategy::Parking] {
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let (tx, rx) = futures::channel::oneshot::channel();
+            let mut pool = BusyLocalPool::with_strategy(8, strategy);
+            pool.spawn(async {
+                yield_now().await;
+                1
+            }.boxed_local()).unwrap();
+            pool.spawn(async move { rx.await.unwrap() }.boxed_local()).unwrap();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(10));
+                tx.send(2).unwrap();
+            });
+            let mut results = futures::executor::block_on(pool.as_task());
+            results.sort();
+            done_tx.send(results).unwrap();
+        });
+        let results = done_rx.recv_timeout(Duration::from_secs(5));
+        assert_eq!(results, Ok(vec![1, 2]), "{:?} pool never woke the outer executor", strategy);
+    }
+}
+
+#[test]
+fn spawn_pool_drains_nested_child() {
+    let cnt = Rc::new(Cell::new(0));
+    let mut child = BusyLocalPool::new(8);
+    for _ in 0..3 {
+        let cnt = cnt.clone();
+        child.spawn(futures::future::lazy(move |_| cnt.set(cnt.get() + 1)).boxed_local()).unwrap();
+    }
+    let mut parent = BusyLocalPool::new(4);
+    parent.spawn(futures::future::ready(()).boxed_local()).unwrap();
+    parent.spawn_pool(child).unwrap();
+
+    assert_eq!(parent.run().len(), 2);
+    assert_eq!(cnt.get(), 3);
+}
+
+#[test]
+fn run_once_reports_each_sweep() {
+    let mut pool = BusyLocalPool::new(8);
+    let mut polls = 0;
+    pool.spawn(futures::future::ready(1).boxed_local()).unwrap();
+    pool.spawn(poll_fn(move |_| {
+        polls += 1;
+        if polls == 2 { Poll::Ready(2) } else { Poll::Pending }
+    }).boxed_local()).unwrap();
+    pool.spawn(futures::future::pending().boxed_local()).unwrap();
+
+    let tick = pool.run_once();
+    assert_eq!(tick.completed, vec![1]);
+    assert!(tick.made_progress);
+    assert_eq!(tick.remaining, 2);
+
+    let tick = pool.run_once();
+    assert_eq!(tick.completed, vec![2]);
+    assert_eq!(tick.remaining, 1);
+
+    let tick = pool.run_once();
+    assert!(tick.completed.is_empty());
+    assert!(!tick.made_progress);
+    assert_eq!(tick.remaining, 1);
+}
+
+#[test]
+fn dropping_task_guard_cancels_task() {
+    let alive = Rc::new(());
+    let mut pool = BusyLocalPool::new(4);
+    let held = alive.clone();
+    let guard = pool.spawn_guarded(async move {
+        let _held = held;
+        futures::future::pending::<()>().await;
+    }.boxed_local()).unwrap();
+
+    assert!(pool.poll_though().is_pending());
+    assert_eq!(Rc::strong_count(&alive), 2);
+
+    drop(guard);
+    assert!(pool.poll_though().is_pending());
+    assert_eq!(Rc::strong_count(&alive), 1);
+    assert!(pool.run().is_empty());
+}
+
+#[test]
+fn guards_dropped_from_other_threads_cancel_running_tasks() {
+    const TASKS: usize = 64;
+    let mut pool = BusyLocalPool::new(TASKS * 2);
+    let mut guards: Vec<_> = (0..TASKS)
+        .map(|_| pool.spawn_guarded(futures::future::pending::<()>().boxed_local()).unwrap())
+        .collect();
+    for _ in 0..TASKS {
+        pool.spawn(futures::future::ready(()).boxed_local()).unwrap();
+    }
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let batch: Vec<_> = guards.drain(..TASKS / 4).collect();
+            std::thread::spawn(move || {
+                for guard in batch {
+                    std::thread::yield_now();
+                    drop(guard);
+                }
+            })
+        })
+        .collect();
+    assert_eq!(pool.run().len(), TASKS);
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let stats = pool.stats();
+    assert_eq!(stats.spawned, TASKS * 2);
+    assert_eq!(stats.completed, TASKS);
+    assert_eq!(stats.cancelled, TASKS);
+}
+
+#[test]
+fn spawner_rejects_tasks_after_pool_is_dropped() {
+    let pool = BusyLocalPool::new(4);
+    let spawner = pool.spawner();
+    assert!(spawner.spawn(futures::future::ready(()).boxed()).is_ok());
+    drop(pool);
+    assert!(spawner.spawn(futures::future::ready(()).boxed()).is_err());
+}
+
+#[test]
+fn spawn_with_id_recycles_slots_of_finished_tasks() {
+    let mut pool = BusyLocalPool::new(8);
+    let mut last = None;
+    for _ in 0..1000 {
+        let ids: Vec<_> = (0..4)
+            .map(|_| pool.spawn_with_id(futures::future::ready(()).boxed_local()).unwrap())
+            .collect();
+        assert!(ids.iter().all(|&id| pool.contains(id)));
+        pool.run();
+        assert!(ids.iter().all(|&id| !pool.contains(id)));
+        last = ids.first().copied();
+    }
+    assert_eq!(pool.id_slots(), 4);
+
+    // a recycled slot does not resurrect a finished task's id
+    let fresh = pool.spawn_with_id(futures::future::pending().boxed_local()).unwrap();
+    assert!(pool.contains(fresh));
+    assert!(!pool.contains(last.unwrap()));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "future spawned twice")]
+fn spawning_the_same_future_twice_is_caught_in_debug() {
+    let mut pool = BusyLocalPool::new(4);
+    let fut: std::pin::Pin<Box<dyn std::future::Future<Output = ()>>> = Box::pin(async {});
+    // SAFETY: not sound in general; the pool must reject the alias before dropping it
+    let alias = unsafe { std::ptr::read(&fut) };
+    pool.spawn(fut).unwrap();
+    pool.spawn(alias).unwrap();
+}
+
+#[test]
+fn begin_drain_rejects_spawns_but_finishes_queued_tasks() {
+    let mut pool = BusyLocalPool::new(4);
+    let spawner = pool.spawner();
+    spawner.spawn(futures::future::ready(()).boxed()).unwrap();
+    pool.spawn(futures::future::ready(()).boxed_local()).unwrap();
+
+    pool.begin_drain();
+    assert!(pool.is_draining());
+    assert!(spawner.spawn(futures::future::ready(()).boxed()).is_err());
+    assert_eq!(pool.run().len(), 2);
+}
+
+#[test]
+#[cfg(feature = "trace-events")]
+fn event_log_records_spawn_poll_and_completion() {
+    use minimal_executor::Event;
+
+    let mut pool = BusyLocalPool::new(4);
+    let mut polls = 0;
+    let a = pool.spawn_with_id(poll_fn(move |_| {
+        polls += 1;
+        if polls == 2 { Poll::Ready(()) } else { Poll::Pending }
+    }).boxed_local()).unwrap();
+    let b = pool.spawn_with_id(futures::future::ready(()).boxed_local()).unwrap();
+    pool.run();
+
+    assert_eq!(pool.events(), vec![
+        Event::Spawned(a),
+        Event::Spawned(b),
+        Event::PollStart(a),
+        Event::PollEnd(a, false),
+        Event::PollStart(b),
+        Event::PollEnd(b, true),
+        Event::Completed(b),
+        Event::PollStart(a),
+        Event::PollEnd(a, true),
+        Event::Completed(a),
+    ]);
+}
+
+#[test]
+fn pause_point_pauses_run_until_resumed() {
+    use minimal_executor::RunOutcome;
+
+    let mut pool = BusyLocalPool::new(4);
+    let pause = pool.pause_point();
+    pool.spawn(async move {
+        pause.await;
+        2
+    }.boxed_local()).unwrap();
+    pool.spawn(futures::future::ready(1).boxed_local()).unwrap();
+
+    assert_eq!(pool.run_pausable(), RunOutcome::Paused(vec![1]));
+    assert_eq!(pool.run_pausable(), RunOutcome::Done(vec![2]));
+}
+
+#[test]
+#[cfg_attr(debug_assertions, should_panic(expected = "already terminated"))]
+fn spawn_checked_skips_terminated_future() {
+    let mut done = futures::future::ready(1).fuse();
+    assert_eq!(minimal_executor::block_on(&mut done), 1);
+
+    let mut pool = BusyLocalPool::new(2);
+    assert_eq!(pool.spawn_checked(futures::future::ready(2).fuse()).ok(), Some(true));
+    assert_eq!(pool.spawn_checked(done).ok(), Some(false));
+    assert_eq!(pool.run(), vec![2]);
+}
+
+#[test]
+fn full_queue_rejects_spawns_instead_of_panicking() {
+    let rejected = Rc::new(Cell::new(0));
+    let mut pool = BusyLocalPool::new(2);
+    let spawner = pool.spawner();
+    let seen = rejected.clone();
+    // while polled, this task is out of the queue but keeps its slot
+    pool.spawn(async move {
+        if spawner.spawn(futures::future::ready(()).boxed()).is_err() {
+            seen.set(seen.get() + 1);
+        }
+    }.boxed_local()).unwrap();
+    pool.spawn(futures::future::ready(()).boxed_local()).unwrap();
+    assert!(pool.spawn(futures::future::ready(()).boxed_local()).is_err());
+    let full = pool.spawn_checked(futures::future::ready(()).fuse()).unwrap_err();
+    assert_eq!(full.kind, minimal_executor::SpawnErrorKind::Full);
+
+    assert_eq!(pool.run().len(), 2);
+    assert_eq!(rejected.get(), 1);
+    assert!(pool.spawn(futures::future::ready(()).boxed_local()).is_ok());
+}
+
+#[test]
+fn with_growth_grows_until_max() {
+    let mut pool = BusyLocalPool::with_growth(2, 6);
+    assert_eq!(pool.to_string(), "pool: 0/2 tasks, 0 completed");
+    for _ in 0..3 {
+        pool.spawn(futures::future::ready(()).boxed_local()).unwrap();
+    }
+    assert_eq!(pool.to_string(), "pool: 3/4 tasks, 0 completed");
+
+    let spawner = pool.spawner();
+    for _ in 0..3 {
+        spawner.spawn(futures::future::ready(()).boxed()).unwrap();
+    }
+    assert!(spawner.spawn(futures::future::ready(()).boxed()).is_err());
+    assert_eq!(pool.to_string(), "pool: 6/6 tasks, 0 completed");
+    assert!(pool.spawn(futures::future::ready(()).boxed_local()).is_err());
+
+    assert_eq!(pool.run().len(), 6);
+    assert!(pool.spawn(futures::future::ready(()).boxed_local()).is_ok());
+}
+
+#[test]
+fn spawner_feeds_pool_with_non_unit_results() {
+    let mut pool = BusyLocalPool::new(4);
+    let spawner = pool.spawner();
+    spawner.spawn(futures::future::ready(7u32).boxed()).unwrap();
+    pool.spawn(futures::future::ready(1u32).boxed_local()).unwrap();
+    let mut results = pool.run();
+    results.sort();
+    assert_eq!(results, vec![1, 7]);
+}
+
+#[test]
+fn drain_hands_back_unfinished_tasks_for_another_executor() {
+    let (tx, rx) = futures::channel::oneshot::channel();
+    let mut pool: BusyLocalPool<'_, u32> = BusyLocalPool::new(8);
+    pool.spawn(async move { rx.await.unwrap() }.boxed_local()).unwrap();
+    pool.spawn_priority(futures::future::pending().boxed_local()).unwrap();
+    let guard = pool.spawn_guarded(futures::future::pending().boxed_local()).unwrap();
+    pool.spawn(async { 1 }.boxed_local()).unwrap();
+    assert_eq!(pool.try_run_one(), Poll::Ready(1));
+    drop(guard);
+
+    let drained = pool.drain();
+    assert_eq!(drained.len(), 2);
+    assert_eq!(pool.stats().cancelled, 1);
+    assert!(pool.run().is_empty());
+
+    let mut other = BusyLocalPool::new(8);
+    for fut in drained {
+        other.spawn(Box::pin(fut)).unwrap();
+    }
+    tx.send(7).unwrap();
+    assert_eq!(other.try_run_one(), Poll::Ready(7));
+    assert!(other.try_run_one().is_pending());
+}
+
+#[test]
+fn priority_tasks_run_before_normal_ones() {
+    let mut pool: BusyLocalPool<'_, u32> = BusyLocalPool::new(4);
+    let spawner = pool.spawner();
+    pool.spawn(async { 1 }.boxed_local()).unwrap();
+    spawner.spawn(Box::pin(async { 2 })).unwrap();
+    pool.spawn_priority(async { 3 }.boxed_local()).unwrap();
+    spawner.spawn_priority(Box::pin(async { 4 })).unwrap();
+    assert_eq!(pool.run(), vec![3, 4, 1, 2]);
+}
+
+#[test]
+fn fairness_polls_normal_tasks_under_constant_priority_load() {
+    let mut unfair: BusyLocalPool<'_, u32> = BusyLocalPool::new(4);
+    unfair.spawn(async { 0 }.boxed_local()).unwrap();
+    for i in 1..=10 {
+        unfair.spawn_priority(async move { i }.boxed_local()).unwrap();
+        assert_eq!(unfair.try_run_one(), Poll::Ready(i));
+    }
+
+    let mut pool: BusyLocalPool<'_, u32> = BusyLocalPool::new(4).with_fairness(3);
+    pool.spawn(async { 0 }.boxed_local()).unwrap();
+    for i in 1..=2 {
+        pool.spawn_priority(async move { i }.boxed_local()).unwrap();
+        assert_eq!(pool.try_run_one(), Poll::Ready(i));
+    }
+    pool.spawn_priority(async { 3 }.boxed_local()).unwrap();
+    // the third sweep in a row is due to start with the normal tasks
+    assert_eq!(pool.try_run_one(), Poll::Ready(0));
+    assert_eq!(pool.try_run_one(), Poll::Ready(3));
+}
+
+#[test]
+fn spinning_task_is_polled_once_per_round() {
+    let polls: Vec<_> = (0..3).map(|_| Rc::new(Cell::new(0))).collect();
+    let mut pool = BusyLocalPool::new(4);
+    let spins = polls[0].clone();
+    pool.spawn(poll_fn(move |cx| {
+        spins.set(spins.get() + 1);
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }).boxed_local()).unwrap();
+    for p in &polls[1..] {
+        pool.spawn(idle_task(p.clone()).boxed_local()).unwrap();
+    }
+
+    for round in 1..=3 {
+        assert!(pool.poll_though().is_pending());
+        assert!(polls.iter().all(|p| p.get() == 2 * round - 1));
+        assert!(!pool.run_once().made_progress);
+        assert!(polls.iter().all(|p| p.get() == 2 * round));
+    }
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn metrics_count_polls_completions_and_requeues() {
+    use minimal_executor::PoolMetrics;
+
+    let polls = Rc::new(Cell::new(0));
+    let mut pool = BusyLocalPool::new(4);
+    pool.spawn(idle_task(polls.clone()).boxed_local()).unwrap();
+    pool.spawn(async {}.boxed_local()).unwrap();
+    pool.spawn(async {}.boxed_local()).unwrap();
+
+    pool.run_once();
+    assert_eq!(pool.metrics(), PoolMetrics { polls: 3, completed: 2, requeued: 1, max_depth: 3 });
+    assert!(pool.poll_though().is_pending());
+    assert_eq!(pool.metrics(), PoolMetrics { polls: 4, completed: 2, requeued: 2, max_depth: 3 });
+}
+
+#[test]
+fn custom_queue_backend_drives_the_pool() {
+    use minimal_executor::TaskQueue;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+
+    /// A fixed-size queue a single-threaded embedded target might use.
+    struct RingBuffer<T> {
+        items: RefCell<VecDeque<T>>,
+        cap: usize,
+    }
+
+    impl<T> TaskQueue<T> for RingBuffer<T> {
+        fn push(&self, value: T) -> Result<(), T> {
+            let mut items = self.items.borrow_mut();
+            if items.len() == self.cap {
+                return Err(value);
+            }
+            items.push_back(value);
+            Ok(())
+        }
+
+        fn pop(&self) -> Option<T> {
+            self.items.borrow_mut().pop_front()
+        }
+
+        fn len(&self) -> usize {
+            self.items.borrow().len()
+        }
+
+        fn capacity(&self) -> usize {
+            self.cap
+        }
+    }
+
+    let ring = |cap| RingBuffer { items: RefCell::new(VecDeque::new()), cap };
+    let mut pool = BusyLocalPool::with_queues(ring(2), ring(1), WakerStrategy::Busy);
+    pool.spawn(async { 1 }.boxed_local()).unwrap();
+    pool.spawn(async { 2 }.boxed_local()).unwrap();
+    let full = pool.spawn(async { 3 }.boxed_local()).unwrap_err();
+    assert_eq!(full.kind, minimal_executor::SpawnErrorKind::Full);
+    pool.spawn_priority(async { 0 }.boxed_local()).unwrap();
+    assert_eq!(pool.run(), vec![0, 1, 2]);
+    pool.spawn(full.into_future()).unwrap();
+    assert_eq!(pool.run(), vec![3]);
+
+    let mut unbounded = BusyLocalPool::with_queues(
+        crossbeam::queue::SegQueue::new(), crossbeam::queue::SegQueue::new(), WakerStrategy::Busy);
+    for i in 0..100 {
+        unbounded.spawn(async move { i }.boxed_local()).unwrap();
+    }
+    assert_eq!(unbounded.run().len(), 100);
+}
+
+#[test]
+fn remaining_capacity_tracks_free_slots() {
+    let mut pool: BusyLocalPool<'_, ()> = BusyLocalPool::new(4);
+    let spawner = pool.spawner();
+    for _ in 0..2 {
+        pool.spawn(futures::future::pending().boxed_local()).unwrap();
+    }
+    spawner.spawn(Box::pin(futures::future::pending())).unwrap();
+    assert_eq!(pool.capacity(), 4);
+    assert_eq!(pool.remaining_capacity(), 1);
+    assert_eq!(spawner.remaining_capacity(), Some(1));
+
+    // a popped task keeps its slot while it is being polled
+    assert!(pool.poll_once().is_pending());
+    assert_eq!(pool.remaining_capacity(), 1);
+    pool.spawn(futures::future::pending().boxed_local()).unwrap();
+    assert_eq!(pool.remaining_capacity(), 0);
+    assert!(pool.spawn(futures::future::pending().boxed_local()).is_err());
+
+    drop(pool);
+    assert_eq!(spawner.remaining_capacity(), None);
+}
+
+#[test]
+fn step_interleaves_tasks_one_poll_at_a_time() {
+    use minimal_executor::StepOutcome;
+
+    let log = Rc::new(std::cell::RefCell::new(Vec::new()));
+    let mut pool = BusyLocalPool::new(4);
+    let mut ids = Vec::new();
+    for name in ["a", "b"] {
+        let log = log.clone();
+        let mut polls = 0;
+        ids.push(pool.spawn_with_id(poll_fn(move |cx| {
+            log.borrow_mut().push(name);
+            polls += 1;
+            if polls == 2 {
+                return Poll::Ready(name);
+            }
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }).boxed_local()).unwrap());
+    }
+
+    for (i, &id) in ids.iter().enumerate() {
+        match pool.step() {
+            StepOutcome::Pending { id: Some(stepped), position, woken } => {
+                assert_eq!(stepped, id);
+                assert_eq!(position, i);
+                // the task woke itself during the poll
+                assert!(woken.read_reset());
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+    // a new round starts over at the first task still queued
+    assert!(matches!(pool.step(), StepOutcome::Ready { position: 0, output: "a", .. }));
+    assert!(matches!(pool.step(), StepOutcome::Ready { position: 1, output: "b", .. }));
+    assert!(matches!(pool.step(), StepOutcome::Empty));
+    assert_eq!(*log.borrow(), ["a", "b", "a", "b"]);
+}
+
+#[test]
+fn step_starts_a_new_round_after_other_drive_loops() {
+    use minimal_executor::StepOutcome;
+
+    let mut pool = BusyLocalPool::new(8);
+    for i in 0..3 {
+        pool.spawn(Box::pin(async move { i })).unwrap();
+    }
+    assert!(matches!(pool.step(), StepOutcome::Ready { position: 0, output: 0, .. }));
+    assert_eq!(pool.run(), vec![1, 2]);
+    pool.spawn(Box::pin(async { 3 })).unwrap();
+    assert!(matches!(pool.step(), StepOutcome::Ready { position: 0, output: 3, .. }));
+
+    for i in 4..6 {
+        pool.spawn(Box::pin(async move { i })).unwrap();
+    }
+    assert!(matches!(pool.step(), StepOutcome::Ready { position: 0, output: 4, .. }));
+    assert_eq!(pool.poll_once(), Poll::Ready(Some(5)));
+    pool.spawn(Box::pin(futures::future::pending())).unwrap();
+    assert!(matches!(pool.step(), StepOutcome::Pending { position: 0, .. }));
+    assert_eq!(pool.drain().len(), 1);
+    pool.spawn(Box::pin(async { 6 })).unwrap();
+    assert!(matches!(pool.step(), StepOutcome::Ready { position: 0, output: 6, .. }));
+}
+
+#[test]
+fn step_drops_cancelled_tasks_and_keeps_wake_ups_for_run() {
+    use minimal_executor::StepOutcome;
+
+    let mut pool = BusyLocalPool::with_strategy(4, WakerStrategy::SingleFlag);
+    let guard = pool.spawn_guarded(Box::pin(futures::future::pending::<u32>())).unwrap();
+    let (tx, rx) = futures::channel::oneshot::channel();
+    pool.spawn(Box::pin(async move { rx.await.unwrap() })).unwrap();
+
+    drop(guard);
+    assert!(matches!(pool.step(), StepOutcome::Cancelled { position: 0, .. }));
+    // tasks spawned with plain `spawn` have no id, but still a position
+    let StepOutcome::Pending { position: 1, woken, .. } = pool.step() else { panic!("task should be pending") };
+    assert!(!woken.read_reset());
+
+    tx.send(4).unwrap();
+    assert!(woken.read_reset());
+    // the wake-up also reached the task's own waker, so `run` polls it
+    assert_eq!(pool.run(), vec![4]);
+}
+
+#[test]
+fn try_spawn_hands_back_rejected_futures() {
+    use minimal_executor::SpawnErrorKind;
+
+    let mut pool = BusyLocalPool::new(1);
+    let spawner = pool.spawner();
+    spawner.try_spawn(Box::pin(async { 1 })).unwrap();
+    let full = spawner.try_spawn(Box::pin(async { 2 })).unwrap_err();
+    assert_eq!(full.kind, SpawnErrorKind::Full);
+    assert_eq!(pool.run(), vec![1]);
+
+    pool.begin_drain();
+    let draining = spawner.try_spawn(full.into_future()).unwrap_err();
+    assert_eq!(draining.kind, SpawnErrorKind::Shutdown);
+    drop(pool);
+    let gone = spawner.try_spawn(draining.future).unwrap_err();
+    assert_eq!(gone.kind, SpawnErrorKind::Shutdown);
+    assert_eq!(futures::executor::block_on(gone.future), 2);
+}
+
+#[test]
+fn spawner_spawn_reports_a_full_queue_as_full() {
+    use futures::task::{Spawn, SpawnExt};
+    use minimal_executor::SpawnErrorKind;
+
+    let mut pool = BusyLocalPool::new(1);
+    let spawner = pool.spawner();
+    spawner.spawn(Box::pin(async {})).unwrap();
+    let full = spawner.spawn(Box::pin(async {})).unwrap_err();
+    assert_eq!(full.kind, SpawnErrorKind::Full);
+    // the priority queue has its own slot
+    spawner.spawn_priority(full.into_future()).unwrap();
+    // the futures `Spawn` trait has no way to say "full"
+    assert!(SpawnExt::spawn(&spawner, async {}).unwrap_err().is_shutdown());
+    assert!(spawner.status().is_ok());
+    assert_eq!(pool.run().len(), 2);
+}
+
+#[test]
+fn pending_ids_lists_unfinished_tasks_and_ids_are_never_reused() {
+    let (tx, rx) = futures::channel::oneshot::channel::<()>();
+    let mut pool = BusyLocalPool::new(4);
+    let waiting = pool.spawn_with_id(Box::pin(async move { rx.await.unwrap() })).unwrap();
+    let mut seen = vec![waiting];
+    for _ in 0..3 {
+        let id = pool.spawn_with_id(Box::pin(async {})).unwrap();
+        assert!(!seen.contains(&id));
+        seen.push(id);
+        while pool.pending_ids().len() > 1 {
+            let _ = pool.poll_once();
+        }
+        assert_eq!(pool.pending_ids(), vec![waiting]);
+    }
+    // the finished tasks all went through the same recycled slot
+    assert_eq!(pool.id_slots(), 2);
+
+    tx.send(()).unwrap();
+    pool.run();
+    assert!(pool.pending_ids().is_empty());
+}
+
+#[test]
+fn poll_observer_sees_every_poll_with_the_task_id() {
+    use minimal_executor::PollState;
+    use std::cell::RefCell;
+
+    let polls = RefCell::new(Vec::new());
+    let mut pool = BusyLocalPool::new(4);
+    let spawner = pool.spawner();
+    pool.set_poll_observer(|id, state| polls.borrow_mut().push((id, state)));
+    let named = pool.spawn_with_id(Box::pin(async {
+        futures::pending!();
+        futures::pending!();
+    })).unwrap();
+    spawner.spawn(Box::pin(async {})).unwrap();
+    pool.run();
+    drop((pool, spawner));
+
+    let polls = polls.into_inner();
+    let states = |id| polls.iter().filter(|(i, _)| *i == id).map(|(_, s)| *s).collect::<Vec<_>>();
+    assert_eq!(states(named), [PollState::Pending, PollState::Pending, PollState::Ready]);
+    let other = polls.iter().find(|(id, _)| *id != named).unwrap().0;
+    assert_eq!(states(other), [PollState::Ready]);
+    assert_eq!(polls.len(), 4);
+}
+
+#[test]
+fn append_moves_what_fits_and_keeps_the_rest() {
+    use minimal_executor::AppendError;
+
+    let mut target = BusyLocalPool::new(3);
+    target.spawn(Box::pin(async { 0 })).unwrap();
+    let mut source = BusyLocalPool::new(8);
+    let (tx, rx) = futures::channel::oneshot::channel::<()>();
+    let waiting = source.spawn_with_id(Box::pin(async move { rx.await.unwrap(); 1 })).unwrap();
+    source.spawn_priority(Box::pin(async { 2 })).unwrap();
+    let guard = source.spawn_guarded(Box::pin(async { 3 })).unwrap();
+    drop(guard);
+    for i in 4..7 {
+        source.spawn(Box::pin(async move { i })).unwrap();
+    }
+
+    // priority tasks have a queue of their own; the cancelled one is dropped
+    assert_eq!(target.append(&mut source), Err(AppendError { left: 2 }));
+    assert_eq!((target.len(), source.len()), (4, 2));
+    assert!(!source.contains(waiting));
+    assert_eq!(source.stats().cancelled, 1);
+    assert_eq!(source.run(), vec![5, 6]);
+
+    tx.send(()).unwrap();
+    let mut results = target.run();
+    results.sort();
+    assert_eq!(results, vec![0, 1, 2, 4]);
+    assert!(target.append(&mut source).is_ok());
+}
+
+#[test]
+fn yield_now_interleaves_tasks() {
+    use minimal_executor::yield_now;
+    use std::cell::RefCell;
+
+    for strategy in [WakerStrategy::Busy, WakerStrategy::SingleFlag] {
+        let log = RefCell::new(Vec::new());
+        let mut pool = BusyLocalPool::with_strategy(4, strategy);
+        for name in ['a', 'b'] {
+            let log = &log;
+            pool.spawn(Box::pin(async move {
+                for i in 0..3 {
+                    log.borrow_mut().push((name, i));
+                    yield_now().await;
+                }
+            })).unwrap();
+        }
+        pool.run();
+        drop(pool);
+        assert_eq!(log.into_inner(), [('a', 0), ('b', 0), ('a', 1), ('b', 1), ('a', 2), ('b', 2)]);
+    }
+}
+
+#[test]
+fn panic_in_run_forgets_ids_of_dropped_tasks() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let mut pool = BusyLocalPool::new(4);
+    let waiting = pool.spawn_with_id(Box::pin(async { futures::future::pending::<()>().await })).unwrap();
+    pool.spawn_with_id(Box::pin(async { panic!("task failed") })).unwrap();
+    assert!(catch_unwind(AssertUnwindSafe(|| pool.run())).is_err());
+    assert!(!pool.contains(waiting));
+    assert!(pool.pending_ids().is_empty());
+    // freed allocations get reused; they must not look like double spawns
+    for _ in 0..8 {
+        pool.spawn(Box::pin(async {})).unwrap();
+        pool.spawn(Box::pin(async {})).unwrap();
+        assert_eq!(pool.run().len(), 2);
+    }
+}
+
+#[test]
+fn panic_in_poll_once_releases_the_task_slot() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let mut pool = BusyLocalPool::new(2);
+    let id = pool.spawn_with_id(Box::pin(async { panic!("task failed") })).unwrap();
+    assert!(catch_unwind(AssertUnwindSafe(|| pool.poll_once())).is_err());
+    assert!(pool.is_empty());
+    assert!(!pool.contains(id));
+    assert_eq!(pool.remaining_capacity(), 2);
+    pool.spawn(Box::pin(async {})).unwrap();
+    pool.spawn(Box::pin(async {})).unwrap();
+    assert_eq!(pool.run().len(), 2);
+}
+
+#[test]
+fn middleware_wraps_every_spawned_task() {
+    use futures::future::LocalFutureObj;
+
+    let polls = Rc::new(Cell::new(0));
+    let layers = Rc::new(Cell::new(0));
+
+    let counter = polls.clone();
+    let applied = layers.clone();
+    let mut pool = BusyLocalPool::new(4)
+        .with_middleware(move |mut fut: LocalFutureObj<'_, u32>| {
+            let counter = counter.clone();
+            LocalFutureObj::new(Box::pin(futures::future::poll_fn(move |cx| {
+                counter.set(counter.get() + 1);
+                fut.poll_unpin(cx)
+            })))
+        })
+        .with_middleware(move |fut| {
+            applied.set(applied.get() + 1);
+            fut
+        });
+
+    pool.spawn(Box::pin(futures::future::lazy(|_| 1))).unwrap();
+    pool.spawn(Box::pin(async {
+        futures::future::ready(()).await;
+        2
+    })).unwrap();
+    pool.spawner().spawn(Box::pin(futures::future::ready(3))).unwrap();
+
+    let mut results = pool.run();
+    results.sort_unstable();
+    assert_eq!(results, vec![1, 2, 3]);
+    assert_eq!(layers.get(), 3);
+    assert_eq!(polls.get(), 3);
+}
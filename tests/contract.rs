@@ -0,0 +1,299 @@
+//! The behavioral contract shared by every pool.
+//!
+//! - `run` returns every result and only returns once the pool is empty, including
+//!   tasks spawned through a spawner before or during the run.
+//! - `poll_once` returns `Ready(None)` only for an empty pool, `Ready(Some(_))` when a
+//!   task completed during the call, and `Pending` otherwise.
+//! - `len` counts every unfinished task, including those spawners queued.
+//! - `poll_with` polls the tasks with the caller's context, so their wake-ups reach it.
+//! - `try_run_one` completes at most one task per call, and returns `Pending` both for
+//!   an empty pool and for a pool with nothing ready.
+//! - `run_n` returns early once nothing more can complete.
+//! - `run_iter` yields results as tasks complete, including tasks spawners send in
+//!   between, and leaves unconsumed tasks in the pool.
+//! - `poll_step` never waits, and returns `Ready(())` only once the pool is empty.
+//! - A panic unwinding out of `run` drops every task still in the pool.
+//!
+//! - `run_bounded` fails with the number of stuck tasks once enough passes in a row
+//!   completed nothing, and otherwise returns every result like `run`.
+//!
+//! Tasks must register a wake-up to be polled again; all pools hang in `run` on a
+//! task that never completes.
+use futures::future::{pending, ready, FutureExt};
+use futures::task::Poll;
+use minimal_executor::{BusyLocalPool, LocalPool, NewLocalPool};
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Waker};
+
+/// Wakes itself `n` times before completing.
+struct Yield(usize);
+
+impl Future for Yield {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 == 0 {
+            return Poll::Ready(());
+        }
+        self.0 -= 1;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Completes once `flag` is set, relying on whoever sets it to wake the stored waker.
+fn wait_for(flag: Rc<Cell<bool>>, waker: Rc<RefCell<Option<Waker>>>) -> impl Future<Output = ()> {
+    futures::future::poll_fn(move |cx| {
+        if flag.get() {
+            Poll::Ready(())
+        } else {
+            *waker.borrow_mut() = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    })
+}
+
+macro_rules! contract {
+    ($name:ident, $pool:expr, $spawn:ident) => {
+        mod $name {
+            use super::*;
+
+            #[test]
+            fn empty_pool_is_done() {
+                let mut pool = $pool;
+                pool.$spawn(ready(0u32).boxed_local());
+                pool.run();
+                assert!(pool.run().is_empty());
+                assert_eq!(pool.poll_once(), Poll::Ready(None));
+                assert!(pool.try_run_one().is_pending());
+            }
+
+            #[test]
+            fn run_collects_every_result() {
+                let mut pool = $pool;
+                for i in 0..5u32 {
+                    pool.$spawn(ready(i).boxed_local());
+                }
+                let mut results = pool.run();
+                results.sort();
+                assert_eq!(results, vec![0, 1, 2, 3, 4]);
+            }
+
+            #[test]
+            fn poll_once_completes_a_ready_task_behind_a_pending_one() {
+                let mut pool = $pool;
+                pool.$spawn(pending::<u32>().boxed_local());
+                pool.$spawn(ready(7u32).boxed_local());
+                assert_eq!(pool.poll_once(), Poll::Ready(Some(7)));
+                assert!(pool.poll_once().is_pending());
+                assert!(pool.try_run_one().is_pending());
+            }
+
+            #[test]
+            fn try_run_one_completes_one_task_per_call() {
+                let done = Rc::new(Cell::new(0));
+                let mut pool = $pool;
+                for i in 0..4u32 {
+                    let done = done.clone();
+                    pool.$spawn(futures::future::lazy(move |_| {
+                        done.set(done.get() + 1);
+                        i
+                    }).boxed_local());
+                }
+                for completed in 1..=4 {
+                    assert!(pool.try_run_one().is_ready());
+                    assert_eq!(done.get(), completed);
+                }
+                assert!(pool.try_run_one().is_pending());
+            }
+
+            #[test]
+            fn run_n_stops_at_max_or_when_nothing_completes() {
+                let mut pool = $pool;
+                for i in 0..3u32 {
+                    pool.$spawn(ready(i).boxed_local());
+                }
+                pool.$spawn(pending().boxed_local());
+
+                assert_eq!(pool.run_n(2).len(), 2);
+                // only one more task can finish; the pending one must not block
+                assert_eq!(pool.run_n(5).len(), 1);
+                assert!(pool.run_n(5).is_empty());
+            }
+
+            #[test]
+            fn len_counts_tasks_spawners_have_not_handed_over() {
+                let mut pool = $pool;
+                let spawner = pool.spawner();
+                assert!(pool.is_empty());
+                pool.$spawn(ready(1u32).boxed_local());
+                spawner.spawn(Box::pin(async { 2 })).unwrap();
+                spawner.spawn(Box::pin(async { 3 })).unwrap();
+                assert_eq!(pool.len(), 3);
+                assert_eq!(pool.run().len(), 3);
+                assert!(pool.is_empty());
+            }
+
+            #[test]
+            fn run_with_sees_every_result() {
+                let mut pool = $pool;
+                for i in 1..=4u32 {
+                    pool.$spawn(ready(i).boxed_local());
+                }
+                let mut sum = 0;
+                pool.run_with(|i| sum += i);
+                assert_eq!(sum, 10);
+                assert!(pool.is_empty());
+            }
+
+            #[test]
+            fn run_iter_stops_early_and_picks_up_spawned_tasks() {
+                let mut pool = $pool;
+                let spawner = pool.spawner();
+                for i in 0..3u32 {
+                    pool.$spawn(ready(i).boxed_local());
+                }
+                let mut iter = pool.run_iter();
+                assert!(iter.next().is_some());
+                spawner.spawn(ready(10u32).boxed()).unwrap();
+                let rest: u32 = iter.sum();
+                assert!(rest >= 10);
+                assert!(pool.is_empty());
+
+                pool.$spawn(ready(1u32).boxed_local());
+                pool.$spawn(ready(2u32).boxed_local());
+                assert_eq!(pool.run_iter().take(1).count(), 1);
+                assert_eq!(pool.len(), 1);
+            }
+
+            #[test]
+            fn run_bounded_reports_stuck_tasks() {
+                use minimal_executor::StuckError;
+
+                let mut pool = $pool;
+                for i in 0..3u32 {
+                    pool.$spawn(ready(i).boxed_local());
+                }
+                pool.$spawn(Yield(5).map(|_| 3u32).boxed_local());
+                let mut results = pool.run_bounded(10).unwrap();
+                results.sort();
+                assert_eq!(results, vec![0, 1, 2, 3]);
+
+                pool.$spawn(pending::<u32>().boxed_local());
+                pool.$spawn(ready(4u32).boxed_local());
+                assert_eq!(pool.run_bounded(3), Err(StuckError { pending: 1 }));
+                assert_eq!(pool.len(), 1);
+            }
+
+            #[test]
+            fn poll_step_runs_ready_tasks_without_waiting() {
+                let flag = Rc::new(Cell::new(false));
+                let waker = Rc::new(RefCell::new(None));
+                let mut pool = $pool;
+                pool.$spawn(ready(1u32).boxed_local());
+                pool.$spawn(wait_for(flag.clone(), waker.clone()).map(|_| 2u32).boxed_local());
+
+                assert!(pool.poll_step().is_pending());
+                assert_eq!(pool.len(), 1);
+                flag.set(true);
+                waker.borrow_mut().take().unwrap().wake();
+                assert_eq!(pool.poll_step(), Poll::Ready(()));
+                assert!(pool.is_empty());
+            }
+
+            #[test]
+            fn poll_with_wakes_the_caller() {
+                use minimal_executor::waker::CountingWaker;
+
+                let counter = CountingWaker::new();
+                let waker = counter.waker();
+                let mut cx = Context::from_waker(&waker);
+                let (tx, rx) = futures::channel::oneshot::channel();
+                let mut pool = $pool;
+                pool.$spawn(async move { rx.await.unwrap() }.boxed_local());
+
+                assert!(pool.poll_with(&mut cx).is_pending());
+                let before = counter.count();
+                tx.send(3u32).unwrap();
+                assert_eq!(counter.count(), before + 1);
+                assert_eq!(pool.poll_with(&mut cx), Poll::Ready(Some(3)));
+            }
+
+            #[test]
+            fn run_drives_self_waking_tasks() {
+                let mut pool = $pool;
+                for i in 0..3u32 {
+                    pool.$spawn(Yield(i as usize * 4).map(move |_| i).boxed_local());
+                }
+                assert_eq!(pool.run().len(), 3);
+            }
+
+            #[test]
+            fn run_completes_task_woken_by_another() {
+                let flag = Rc::new(Cell::new(false));
+                let waker = Rc::new(RefCell::new(None::<Waker>));
+                let mut pool = $pool;
+                pool.$spawn(wait_for(flag.clone(), waker.clone()).map(|_| 1u32).boxed_local());
+                assert!(pool.poll_once().is_pending());
+                pool.$spawn(async move {
+                    flag.set(true);
+                    if let Some(waker) = waker.borrow_mut().take() {
+                        waker.wake();
+                    }
+                    2u32
+                }.boxed_local());
+                let mut results = pool.run();
+                results.sort();
+                assert_eq!(results, vec![1, 2]);
+            }
+
+            #[test]
+            fn run_picks_up_spawner_tasks() {
+                let cnt = Arc::new(AtomicUsize::new(0));
+                let mut pool = $pool;
+                let spawner = pool.spawner();
+                let c = cnt.clone();
+                spawner.spawn(async move {
+                    c.fetch_add(1, Ordering::Relaxed);
+                }.boxed()).unwrap();
+                let c = cnt.clone();
+                pool.$spawn(async move {
+                    spawner.spawn(async move {
+                        c.fetch_add(1, Ordering::Relaxed);
+                    }.boxed()).unwrap();
+                }.boxed_local());
+                assert_eq!(pool.run().len(), 3);
+                assert_eq!(cnt.load(Ordering::Relaxed), 2);
+                assert_eq!(pool.poll_once(), Poll::Ready(None));
+            }
+
+            #[test]
+            fn panic_in_run_drops_remaining_tasks() {
+                use std::panic::{catch_unwind, AssertUnwindSafe};
+
+                let held = Rc::new(());
+                let clone = held.clone();
+                let mut pool = $pool;
+                pool.$spawn(async move {
+                    pending::<()>().await;
+                    drop(clone);
+                    0u32
+                }.boxed_local());
+                pool.$spawn(async { panic!("task failed") }.boxed_local());
+
+                assert!(catch_unwind(AssertUnwindSafe(|| pool.run())).is_err());
+                assert_eq!(Rc::strong_count(&held), 1);
+                assert!(pool.run().is_empty());
+            }
+        }
+    };
+}
+
+contract!(old, LocalPool::new(), spawn);
+contract!(new, NewLocalPool::new(), spawn);
+// the busy pool's `spawn` is fallible
+contract!(busy, BusyLocalPool::new(16), spawn_or_panic);
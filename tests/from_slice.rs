@@ -0,0 +1,58 @@
+// The event log grows as it records, so the count is only fixed without it.
+#![cfg(not(feature = "trace-events"))]
+
+use core::future::Future;
+use core::pin::{pin, Pin};
+use minimal_executor::BusyLocalPool;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// Counts the allocations made by the current thread.
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+/// The shared state, its two task queues and the pause flag, plus the set of live
+/// future addresses debug builds keep.
+const POOL_ALLOCATIONS: usize = if cfg!(debug_assertions) { 5 } else { 4 };
+
+/// Allocations made while running `tasks` through `from_slice`.
+fn run_counted<'a>(tasks: &'a mut [Pin<&'a mut dyn Future<Output = u32>>]) -> (u32, usize) {
+    let before = allocations();
+    let sum = BusyLocalPool::from_slice(tasks).run_fold(0, |sum, i| sum + i);
+    (sum, allocations() - before)
+}
+
+#[test]
+fn from_slice_only_makes_the_pool_allocations() {
+    let mut one: [Pin<&mut dyn Future<Output = u32>>; 1] = [pin!(async { 1 })];
+    let (sum, allocated) = run_counted(&mut one);
+    assert_eq!(sum, 1);
+    assert_eq!(allocated, POOL_ALLOCATIONS);
+
+    let (a, b, c) = (pin!(async { 1 }), pin!(async { 2 }), pin!(async { 3 }));
+    let mut three: [Pin<&mut dyn Future<Output = u32>>; 3] = [a, b, c];
+    let (sum, allocated) = run_counted(&mut three);
+    assert_eq!(sum, 6);
+    assert_eq!(allocated, POOL_ALLOCATIONS);
+}
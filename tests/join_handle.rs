@@ -0,0 +1,97 @@
+use futures::channel::oneshot::Canceled;
+use minimal_executor::NewLocalPool;
+
+#[test]
+fn join_handle_resolves_after_pool_runs_task() {
+    let mut pool: NewLocalPool<()> = NewLocalPool::new();
+    let spawner = pool.spawner();
+    let handle = spawner.spawn_with_handle(async { "done" }).unwrap();
+    let mut handle = Box::pin(handle);
+    assert!(minimal_executor::poll_on(handle.as_mut()).is_pending());
+
+    pool.run();
+    assert_eq!(minimal_executor::block_on(handle), Ok("done"));
+}
+
+#[test]
+fn join_handle_errors_when_pool_dropped_first() {
+    let pool: NewLocalPool<()> = NewLocalPool::new();
+    let handle = pool.spawner().spawn_with_handle(async { 1 }).unwrap();
+    drop(pool);
+    assert_eq!(handle.block(), Err(Canceled));
+}
+
+macro_rules! abortable {
+    ($name:ident, $pool:expr $(, $unwrap:ident)?) => {
+        mod $name {
+            use super::*;
+            use std::cell::RefCell;
+            use std::rc::Rc;
+
+            #[test]
+            fn abort_drops_task_without_polling_it() {
+                let held = Rc::new(());
+                let polled = Rc::new(RefCell::new(false));
+                let (clone, flag) = (held.clone(), polled.clone());
+                let mut pool = $pool;
+                let (abort, handle) = pool.spawn_abortable(async move {
+                    *flag.borrow_mut() = true;
+                    drop(clone);
+                })$(.$unwrap())?;
+                abort.abort();
+                pool.run();
+
+                assert!(!*polled.borrow());
+                assert_eq!(Rc::strong_count(&held), 1);
+                assert_eq!(handle.block(), Err(Canceled));
+            }
+
+            #[test]
+            fn abort_after_completion_is_a_no_op() {
+                let mut pool = $pool;
+                let (abort, handle) = pool.spawn_abortable(async { 3 })$(.$unwrap())?;
+                pool.run();
+                abort.abort();
+                assert_eq!(handle.block(), Ok(3));
+            }
+
+            #[test]
+            fn abort_mid_poll_drops_the_future() {
+                let held = Rc::new(());
+                let slot: Rc<RefCell<Option<futures::future::AbortHandle>>> = Rc::default();
+                let (clone, own) = (held.clone(), slot.clone());
+                let mut pool = $pool;
+                let (abort, handle) = pool.spawn_abortable(async move {
+                    own.borrow_mut().take().unwrap().abort();
+                    futures::future::pending::<()>().await;
+                    drop(clone);
+                })$(.$unwrap())?;
+                *slot.borrow_mut() = Some(abort);
+                pool.run();
+
+                assert_eq!(Rc::strong_count(&held), 1);
+                assert_eq!(handle.block(), Err(Canceled));
+            }
+        }
+    };
+}
+
+abortable!(old, minimal_executor::LocalPool::new());
+abortable!(new, NewLocalPool::new());
+abortable!(busy, minimal_executor::BusyLocalPool::new(4), unwrap);
+
+#[test]
+fn new_spawner_feeds_pool_from_another_thread() {
+    use futures::FutureExt;
+
+    let mut pool: NewLocalPool<u32> = NewLocalPool::new();
+    let spawner = pool.spawner();
+    std::thread::spawn(move || {
+        for i in 0..3 {
+            spawner.clone().spawn(futures::future::ready(i).boxed()).unwrap();
+        }
+    }).join().unwrap();
+    let mut results = pool.run();
+    results.sort();
+    assert_eq!(results, vec![0, 1, 2]);
+}
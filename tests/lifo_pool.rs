@@ -0,0 +1,54 @@
+use futures::future::{pending, ready, FutureExt};
+use futures::task::Poll;
+use minimal_executor::{BusyLocalPool, LifoLocalPool};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[test]
+fn completion_order_is_reversed_compared_to_fifo() {
+    let mut lifo = LifoLocalPool::new();
+    let mut fifo = BusyLocalPool::new(4);
+    for i in 0..4 {
+        lifo.spawn(ready(i).boxed_local());
+        fifo.spawn(ready(i).boxed_local()).unwrap();
+    }
+    assert_eq!(fifo.run(), vec![0, 1, 2, 3]);
+    assert_eq!(lifo.run(), vec![3, 2, 1, 0]);
+}
+
+#[test]
+fn children_run_before_older_siblings() {
+    let order = Rc::new(RefCell::new(Vec::new()));
+    let mut pool = LifoLocalPool::new();
+    let spawner = pool.spawner();
+    for name in ["a", "b"] {
+        let order = order.clone();
+        let spawner = spawner.clone();
+        pool.spawn(async move {
+            order.borrow_mut().push(name);
+            // the child is `Send`, so the log goes through a channel
+            let (tx, rx) = futures::channel::oneshot::channel();
+            spawner.spawn(Box::pin(async move { tx.send(()).unwrap() })).unwrap();
+            rx.await.unwrap();
+            order.borrow_mut().push(name);
+        }.boxed_local());
+    }
+    pool.run();
+    // "b" finishes with its child before "a" is even started
+    assert_eq!(*order.borrow(), ["b", "b", "a", "a"]);
+}
+
+#[test]
+fn try_run_one_skips_pending_tasks() {
+    let mut pool = LifoLocalPool::new();
+    pool.spawn(ready(1).boxed_local());
+    pool.spawn(pending().boxed_local());
+    assert_eq!(pool.try_run_one(), Poll::Ready(1));
+    assert!(pool.try_run_one().is_pending());
+    assert_eq!(pool.len(), 1);
+
+    drop(pool.spawner());
+    let spawner = pool.spawner();
+    drop(pool);
+    assert!(spawner.spawn(Box::pin(async { 2 })).is_err());
+}
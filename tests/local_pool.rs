@@ -6,7 +6,7 @@ use std::pin::Pin;
 use std::rc::Rc;
 use futures::FutureExt;
 
-struct Pending(Rc<()>);
+struct Pending(#[allow(dead_code)] Rc<()>);
 
 impl Future for Pending {
     type Output = ();
@@ -98,3 +98,897 @@ fn try_run_one_executes_one_ready() {
     }
     assert!(pool.try_run_one().is_pending());
 }
+
+#[test]
+fn spawn_shared_runs_once_for_many_awaiters() {
+    let runs = Rc::new(Cell::new(0));
+    let seen = Rc::new(std::cell::RefCell::new(Vec::new()));
+
+    let mut pool = LocalPool::new();
+    let counter = runs.clone();
+    let shared = pool.spawn_shared(lazy(move |_| {
+        counter.set(counter.get() + 1);
+        7
+    }));
+
+    for _ in 0..2 {
+        let handle = shared.clone();
+        let seen = seen.clone();
+        pool.spawn(Box::pin(async move {
+            let value = handle.await;
+            seen.borrow_mut().push(value);
+        }));
+    }
+    pool.run();
+
+    assert_eq!(runs.get(), 1);
+    assert_eq!(*seen.borrow(), vec![7, 7]);
+}
+
+#[test]
+fn display_counts_spawned_and_queued_tasks() {
+    let mut pool: LocalPool<()> = LocalPool::new();
+    pool.spawn(Box::pin(pending()));
+    pool.spawner().spawn(Box::pin(futures::future::ready(()))).unwrap();
+    assert_eq!(pool.to_string(), "pool: 2 tasks, 0 completed, 0 overdue");
+    assert_eq!(pool.try_run_one(), Poll::Ready(()));
+    assert_eq!(pool.to_string(), "pool: 1 tasks, 1 completed, 0 overdue");
+}
+
+#[test]
+fn display_reports_task_limit_as_capacity() {
+    use minimal_executor::NewLocalPool;
+
+    let mut old: LocalPool<()> = LocalPool::new().with_task_limit(4);
+    old.spawn(Box::pin(pending()));
+    assert_eq!(old.to_string(), "pool: 1/4 tasks, 0 completed, 0 overdue");
+
+    let mut new: NewLocalPool<()> = NewLocalPool::new().with_task_limit(4);
+    new.spawn(Box::pin(pending()));
+    new.spawn(Box::pin(futures::future::ready(())));
+    assert_eq!(new.try_run_one(), Poll::Ready(()));
+    assert_eq!(new.to_string(), "pool: 1/4 tasks, 1 completed");
+}
+
+#[test]
+fn run_into_channel_streams_results_to_consumer() {
+    const ITER: usize = 50;
+
+    let (tx, rx) = crossbeam::channel::bounded(4);
+    let consumer = std::thread::spawn(move || rx.iter().collect::<Vec<usize>>());
+
+    let mut pool = LocalPool::new();
+    for i in 0..ITER {
+        pool.spawn(Box::pin(lazy(move |_| i)));
+    }
+    pool.run_into_channel(tx).unwrap();
+
+    let mut received = consumer.join().unwrap();
+    received.sort_unstable();
+    assert_eq!(received, (0..ITER).collect::<Vec<_>>());
+}
+
+#[test]
+fn run_into_channel_stops_when_receiver_is_gone() {
+    let (tx, rx) = crossbeam::channel::unbounded();
+    drop(rx);
+
+    let mut pool = LocalPool::new();
+    pool.spawn(Box::pin(lazy(|_| 1)));
+    pool.spawn(Box::pin(lazy(|_| 2)));
+    assert!(pool.run_into_channel(tx).is_err());
+}
+
+#[test]
+#[cfg_attr(debug_assertions, should_panic(expected = "already terminated"))]
+fn spawn_checked_rejects_terminated_future() {
+    let mut done = futures::future::ready(1).fuse();
+    assert_eq!(minimal_executor::block_on(&mut done), 1);
+
+    let mut pool = LocalPool::new();
+    assert!(pool.spawn_checked(futures::future::ready(2).fuse()));
+    assert!(!pool.spawn_checked(done));
+    assert_eq!(pool.run(), vec![2]);
+}
+
+#[test]
+fn spawn_to_sink_routes_results_per_task() {
+    let evens = Rc::new(Cell::new(0));
+    let odds = Rc::new(Cell::new(0));
+
+    let mut pool = LocalPool::new();
+    let sink = evens.clone();
+    pool.spawn_to_sink(lazy(|_| 2), move |v| sink.set(sink.get() + v));
+    let sink = odds.clone();
+    pool.spawn_to_sink(lazy(|_| 3), move |v| sink.set(sink.get() + v));
+    pool.run();
+
+    assert_eq!(evens.get(), 2);
+    assert_eq!(odds.get(), 3);
+}
+
+#[test]
+fn poll_once_ingest_counts_spawned_tasks() {
+    let mut pool: LocalPool<()> = LocalPool::new();
+    let spawner = pool.spawner();
+    spawner.spawn(Box::pin(futures::future::ready(()))).unwrap();
+    spawner.spawn(Box::pin(futures::future::ready(()))).unwrap();
+
+    let (ingested, ret) = pool.poll_once_ingest();
+    assert_eq!(ingested, 2);
+    assert!(ret.is_ready());
+
+    let (ingested, _) = pool.poll_once_ingest();
+    assert_eq!(ingested, 0);
+}
+
+#[test]
+fn run_recovering_returns_stalled_futures() {
+    let mut pool = LocalPool::new();
+    pool.spawn(Box::pin(lazy(|_| 1)));
+    pool.spawn(Box::pin(async {
+        pending().await;
+        2
+    }));
+
+    let (results, stalled) = pool.run_recovering();
+    assert_eq!(results, vec![1]);
+    assert_eq!(stalled.len(), 1);
+    assert!(pool.try_run_one().is_pending());
+}
+
+#[test]
+fn run_recovering_takes_stalled_deadline_tasks() {
+    use std::time::{Duration, Instant};
+
+    let mut pool = LocalPool::new().with_task_limit(2);
+    pool.spawn(Box::pin(pending().map(|_| 1)));
+    pool.spawn_deadline(Box::pin(pending().map(|_| 2)), Instant::now() + Duration::from_secs(60));
+
+    let (results, stalled) = pool.run_recovering();
+    assert!(results.is_empty());
+    assert_eq!(stalled.len(), 2);
+    assert!(pool.is_empty());
+    // both slots of the task limit were released
+    let spawner = pool.spawner();
+    spawner.try_spawn(Box::pin(async { 3 })).unwrap();
+    spawner.try_spawn(Box::pin(async { 4 })).unwrap();
+}
+
+#[test]
+fn run_recovering_keeps_yielding_tasks_running() {
+    let mut pool = LocalPool::new();
+    pool.spawn(Box::pin(async {
+        for _ in 0..3 {
+            let mut yielded = false;
+            futures::future::poll_fn(|cx| {
+                if yielded {
+                    return Poll::Ready(());
+                }
+                yielded = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }).await;
+        }
+        3
+    }));
+
+    let (results, stalled) = pool.run_recovering();
+    assert_eq!(results, vec![3]);
+    assert!(stalled.is_empty());
+}
+
+#[test]
+fn on_empty_fires_once_after_last_task() {
+    let fired = Rc::new(Cell::new(0));
+    let done = Rc::new(Cell::new(0));
+
+    let mut pool = LocalPool::new();
+    for _ in 0..3 {
+        let done = done.clone();
+        pool.spawn(Box::pin(lazy(move |_| done.set(done.get() + 1))));
+    }
+    let (signal, seen) = (fired.clone(), done.clone());
+    pool.on_empty(move || {
+        assert_eq!(seen.get(), 3);
+        signal.set(signal.get() + 1);
+    });
+
+    pool.run();
+    pool.run();
+    assert_eq!(fired.get(), 1);
+}
+
+#[test]
+fn spawn_retry_surfaces_eventual_success() {
+    let attempts = Rc::new(Cell::new(0));
+
+    let mut pool = LocalPool::new();
+    let counter = attempts.clone();
+    pool.spawn_retry(move || {
+        counter.set(counter.get() + 1);
+        let attempt = counter.get();
+        lazy(move |_| if attempt < 3 { Err("flaky") } else { Ok(attempt) })
+    }, 5);
+
+    assert_eq!(pool.run(), vec![Ok(3)]);
+    assert_eq!(attempts.get(), 3);
+}
+
+#[test]
+fn spawn_retry_gives_up_after_retries() {
+    let mut pool: LocalPool<Result<(), &str>> = LocalPool::new();
+    pool.spawn_retry(|| lazy(|_| Err("down")), 2);
+    assert_eq!(pool.run(), vec![Err("down")]);
+}
+
+#[test]
+fn run_grouped_buckets_results_by_key() {
+    let mut pool = LocalPool::new();
+    pool.spawn_keyed("even", lazy(|_| 2));
+    pool.spawn_keyed("odd", lazy(|_| 1));
+    pool.spawn_keyed("even", lazy(|_| 4));
+
+    let mut groups = pool.run_grouped();
+    groups.get_mut("even").unwrap().sort_unstable();
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups["even"], vec![2, 4]);
+    assert_eq!(groups["odd"], vec![1]);
+}
+
+#[test]
+fn run_until_any_returns_first_completion() {
+    let mut pool = LocalPool::new();
+    for _ in 0..3 {
+        pool.spawn(Box::pin(async {
+            pending().await;
+            "slow"
+        }));
+    }
+    pool.spawn(Box::pin(lazy(|_| "fast")));
+
+    assert_eq!(pool.run_until_any(), Some("fast"));
+    assert_eq!(pool.to_string(), "pool: 3 tasks, 1 completed, 0 overdue");
+    assert_eq!(pool.run_until_any(), None);
+}
+
+#[test]
+fn middleware_wraps_every_spawned_task() {
+    use futures::future::LocalFutureObj;
+
+    let polls = Rc::new(Cell::new(0));
+    let layers = Rc::new(Cell::new(0));
+
+    let counter = polls.clone();
+    let applied = layers.clone();
+    let mut pool = LocalPool::new()
+        .with_middleware(move |mut fut: LocalFutureObj<'_, u32>| {
+            let counter = counter.clone();
+            LocalFutureObj::new(Box::pin(futures::future::poll_fn(move |cx| {
+                counter.set(counter.get() + 1);
+                fut.poll_unpin(cx)
+            })))
+        })
+        .with_middleware(move |fut| {
+            applied.set(applied.get() + 1);
+            fut
+        });
+
+    pool.spawn(Box::pin(lazy(|_| 1)));
+    pool.spawn(Box::pin(async {
+        futures::future::ready(()).await;
+        2
+    }));
+    pool.spawner().spawn(Box::pin(futures::future::ready(3))).unwrap();
+
+    let mut results = pool.run();
+    results.sort_unstable();
+    assert_eq!(results, vec![1, 2, 3]);
+    assert_eq!(layers.get(), 3);
+    assert_eq!(polls.get(), 3);
+}
+
+#[test]
+fn spawn_with_deadline_handle_reports_timeout() {
+    use minimal_executor::Timeout;
+    use std::time::{Duration, Instant};
+
+    let deadline = Instant::now() + Duration::from_millis(20);
+    let mut pool = LocalPool::new();
+    let on_time = pool.spawn_with_deadline_handle(lazy(|_| 1), deadline);
+    // never woken, so only the deadline sweep can drop it
+    let late = pool.spawn_with_deadline_handle(futures::future::pending::<i32>(), deadline);
+    pool.run();
+
+    assert_eq!(futures::executor::block_on(on_time), Ok(1));
+    assert_eq!(futures::executor::block_on(late), Err(Timeout));
+    assert!(pool.take_timed_out().is_empty());
+    assert_eq!(pool.to_string(), "pool: 0 tasks, 1 completed, 1 overdue");
+}
+
+#[test]
+fn spawn_with_deadline_handle_counts_completion_on_the_last_poll() {
+    use std::time::{Duration, Instant};
+
+    let mut pool = LocalPool::new();
+    let handle = pool.spawn_with_deadline_handle(futures::future::lazy(|_| {
+        std::thread::sleep(Duration::from_millis(20));
+        2
+    }), Instant::now() + Duration::from_millis(5));
+    pool.run();
+
+    assert_eq!(futures::executor::block_on(handle), Ok(2));
+}
+
+#[test]
+fn run_with_reactor_wakes_tasks_on_events() {
+    use std::cell::RefCell;
+    use std::task::Waker;
+
+    let ready = Rc::new(Cell::new(false));
+    let waiting: Rc<RefCell<Option<Waker>>> = Rc::default();
+
+    let (flag, slot) = (ready.clone(), waiting.clone());
+    let mut pool = LocalPool::new();
+    pool.spawn(futures::future::poll_fn(move |cx| {
+        if flag.get() {
+            Poll::Ready(1)
+        } else {
+            *slot.borrow_mut() = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }).boxed_local());
+
+    let mut ticks = 0;
+    let reactor = futures::future::poll_fn(move |_| {
+        ticks += 1;
+        if ticks == 3 {
+            // simulate the event source reporting readiness
+            ready.set(true);
+            if let Some(waker) = waiting.borrow_mut().take() {
+                waker.wake();
+            }
+        }
+        Poll::<()>::Pending
+    });
+    assert_eq!(pool.run_with_reactor(reactor), vec![1]);
+}
+
+#[test]
+fn spawn_detached_discards_output() {
+    let mut pool: LocalPool<()> = LocalPool::new();
+    pool.spawn_detached(futures::future::ready(42));
+    assert_eq!(pool.run(), vec![()]);
+}
+
+#[test]
+fn run_fold_matches_run_sum() {
+    let spawn_all = |pool: &mut LocalPool<u64>| {
+        for i in 0..50 {
+            pool.spawn(lazy(move |_| i * 3).boxed_local());
+        }
+    };
+    let mut pool = LocalPool::new();
+    spawn_all(&mut pool);
+    let folded = pool.run_fold(0, |sum, i| sum + i);
+    spawn_all(&mut pool);
+    assert_eq!(folded, pool.run().into_iter().sum::<u64>());
+}
+
+#[test]
+fn results_leaves_pool_usable_after_partial_iteration() {
+    let mut pool = LocalPool::new();
+    for i in 0..4 {
+        pool.spawn(lazy(move |_| i).boxed_local());
+    }
+    let first_two: Vec<_> = pool.results().take(2).collect();
+    assert_eq!(first_two.len(), 2);
+
+    let mut rest = pool.run();
+    rest.extend(first_two);
+    rest.sort();
+    assert_eq!(rest, vec![0, 1, 2, 3]);
+
+    pool.spawn(pending().map(|_| 9).boxed_local());
+    let mut results = pool.results();
+    assert_eq!(results.next(), None);
+    assert!(results.is_stalled());
+}
+
+#[test]
+fn spawn_stream_task_yields_every_item_as_a_result() {
+    let mut pool = LocalPool::new();
+    pool.spawn_stream_task(futures::stream::iter(vec![1, 2, 3]));
+    pool.spawn(lazy(|_| 4).boxed_local());
+    let mut results = pool.run();
+    results.sort();
+    assert_eq!(results, vec![1, 2, 3, 4]);
+}
+
+#[test]
+fn spawner_sink_accepts_forwarded_futures() {
+    use futures::future::FutureObj;
+    use futures::StreamExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let cnt = Arc::new(AtomicUsize::new(0));
+    let mut pool: LocalPool<()> = LocalPool::new();
+    let futures = (0..3).map(|_| {
+        let cnt = cnt.clone();
+        Ok(FutureObj::new(async move {
+            cnt.fetch_add(1, Ordering::Relaxed);
+        }.boxed()))
+    });
+    futures::executor::block_on(futures::stream::iter(futures).forward(pool.spawner())).unwrap();
+
+    assert_eq!(pool.run().len(), 3);
+    assert_eq!(cnt.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn run_adaptive_uses_less_cpu_than_run_on_idle_pool() {
+    use std::time::Duration;
+
+    /// Nanoseconds the current thread has spent on the CPU.
+    fn cpu_time() -> u64 {
+        let stat = std::fs::read_to_string("/proc/thread-self/schedstat").unwrap();
+        stat.split_whitespace().next().unwrap().parse().unwrap()
+    }
+
+    /// A task woken from another thread after a delay.
+    fn sleeper() -> futures::channel::oneshot::Receiver<()> {
+        let (tx, rx) = futures::channel::oneshot::channel();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            tx.send(()).unwrap();
+        });
+        rx
+    }
+
+    let mut pool = LocalPool::new();
+    pool.spawn(sleeper().map(|r| r.unwrap()).boxed_local());
+    let start = cpu_time();
+    pool.run();
+    let spinning = cpu_time() - start;
+
+    pool.spawn(sleeper().map(|r| r.unwrap()).boxed_local());
+    let start = cpu_time();
+    assert_eq!(pool.run_adaptive().len(), 1);
+    let adaptive = cpu_time() - start;
+
+    assert!(adaptive * 2 < spinning, "adaptive {}ns vs spinning {}ns", adaptive, spinning);
+}
+
+#[test]
+fn spawn_tracked_waits_for_transitive_children() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Completes after waking itself `polls` times.
+    fn child(polls: usize, done: Arc<AtomicUsize>) -> impl Future<Output = ()> + Send {
+        let mut left = polls;
+        futures::future::poll_fn(move |cx| {
+            if left == 0 {
+                done.fetch_add(1, Ordering::Relaxed);
+                return Poll::Ready(());
+            }
+            left -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+    }
+
+    let done = Arc::new(AtomicUsize::new(0));
+    let mut pool: LocalPool<()> = LocalPool::new();
+    let spawner = pool.spawner();
+    let local = pool.local_spawner();
+    let children = done.clone();
+    let mut handle = pool.spawn_tracked(async move {
+        spawner.spawn(child(2, children.clone()).boxed()).unwrap();
+        spawner.spawn(child(5, children.clone()).boxed()).unwrap();
+        // a `!Send` child, which can only go through the local spawner
+        let marker = Rc::new(());
+        local.spawn(async move {
+            child(20, children).await;
+            drop(marker);
+        }.boxed_local()).unwrap();
+    });
+
+    assert_eq!(pool.poll_once(), Poll::Ready(Some(())));
+    assert!((&mut handle).now_or_never().is_none());
+    while done.load(Ordering::Relaxed) < 2 {
+        let _ = pool.poll_once();
+    }
+    // the local child is still running
+    assert!((&mut handle).now_or_never().is_none());
+    pool.run();
+    assert_eq!(done.load(Ordering::Relaxed), 3);
+    assert!(handle.now_or_never().is_some());
+}
+
+#[test]
+fn spawn_tracked_ignores_spawns_into_other_pools() {
+    let other: LocalPool<()> = LocalPool::new();
+    let elsewhere = other.spawner();
+    let mut pool: LocalPool<()> = LocalPool::new();
+    let handle = pool.spawn_tracked(async move {
+        elsewhere.spawn(futures::future::pending().boxed()).unwrap();
+    });
+    pool.run();
+    assert!(handle.now_or_never().is_some());
+    assert_eq!(other.len(), 1);
+}
+
+#[test]
+fn every_drive_loop_drops_remaining_tasks_on_panic() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::time::{Duration, Instant};
+
+    fn fail() -> u32 {
+        panic!("task failed")
+    }
+
+    type Drive = fn(&mut LocalPool<'static, u32>);
+
+    let loops: [(&str, Drive); 9] = [
+        ("run_until", |pool| pool.run_until(futures::future::pending())),
+        ("run_with_reactor", |pool| { let _ = pool.run_with_reactor(futures::future::pending()); }),
+        ("run_adaptive", |pool| { let _ = pool.run_adaptive(); }),
+        ("run_into_channel", |pool| { let _ = pool.run_into_channel(crossbeam::channel::unbounded().0); }),
+        ("run_recovering", |pool| { let _ = pool.run_recovering(); }),
+        ("run_until_any", |pool| { let _ = pool.run_until_any(); }),
+        ("run_n", |pool| { let _ = pool.run_n(2); }),
+        ("run_bounded", |pool| { let _ = pool.run_bounded(4); }),
+        ("run_with_deadlines", |pool| { let _ = pool.run_with_deadlines(); }),
+    ];
+    for (name, drive) in loops {
+        let held = Rc::new(());
+        let clone = held.clone();
+        let mut pool = LocalPool::new();
+        pool.spawn(Box::pin(async move {
+            pending().await;
+            drop(clone);
+            0
+        }));
+        let deadline = Instant::now() + Duration::from_secs(60);
+        pool.spawn_deadline(Box::pin(pending().map(|_| 1)), deadline);
+        pool.spawn(Box::pin(lazy(|_| fail())));
+
+        assert!(catch_unwind(AssertUnwindSafe(|| drive(&mut pool))).is_err(), "{}", name);
+        assert_eq!(Rc::strong_count(&held), 1, "{}", name);
+        assert!(pool.is_empty(), "{}", name);
+    }
+}
+
+#[test]
+fn spawn_tracked_panic_does_not_capture_later_spawns() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let mut pool: LocalPool<()> = LocalPool::new();
+    let spawner = pool.spawner();
+    let mut handle = pool.spawn_tracked(async { panic!("tracked task failed") });
+    assert!(catch_unwind(AssertUnwindSafe(|| pool.run())).is_err());
+    assert!((&mut handle).now_or_never().is_some());
+
+    // spawned outside of any tracked task, so it joins no group
+    spawner.spawn(futures::future::pending().boxed()).unwrap();
+    assert!(handle.now_or_never().is_some());
+}
+
+#[test]
+fn with_task_limit_stops_runaway_spawning() {
+    use futures::future::BoxFuture;
+    use minimal_executor::Spawner;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Spawns two copies of itself every time it runs.
+    fn runaway(spawner: Spawner<()>, spawned: Arc<AtomicUsize>, rejected: Arc<AtomicUsize>) -> BoxFuture<'static, ()> {
+        async move {
+            // the bug keeps spawning; only stop once the limit kicked in so the test ends
+            if rejected.load(Ordering::Relaxed) > 0 {
+                return;
+            }
+            for _ in 0..2 {
+                let child = runaway(spawner.clone(), spawned.clone(), rejected.clone());
+                match spawner.spawn(child) {
+                    Ok(()) => spawned.fetch_add(1, Ordering::Relaxed),
+                    Err(_) => rejected.fetch_add(1, Ordering::Relaxed),
+                };
+            }
+        }.boxed()
+    }
+
+    let spawned = Arc::new(AtomicUsize::new(0));
+    let rejected = Arc::new(AtomicUsize::new(0));
+    let mut pool = LocalPool::new().with_task_limit(16);
+    let spawner = pool.spawner();
+    pool.spawn(runaway(spawner, spawned.clone(), rejected.clone()));
+    pool.run();
+
+    assert!(rejected.load(Ordering::Relaxed) > 0);
+    assert!(spawned.load(Ordering::Relaxed) < 32);
+}
+
+#[test]
+fn run_until_returns_once_target_sees_counter() {
+    let count = Rc::new(Cell::new(0));
+
+    let mut pool: LocalPool<()> = LocalPool::new();
+    let counter = count.clone();
+    pool.spawn(Box::pin(futures::future::poll_fn(move |cx| {
+        counter.set(counter.get() + 1);
+        cx.waker().wake_by_ref();
+        Poll::<()>::Pending
+    })));
+
+    let seen = count.clone();
+    let reached = pool.run_until(futures::future::poll_fn(|_| {
+        if seen.get() >= 5 { Poll::Ready(seen.get()) } else { Poll::Pending }
+    }));
+    assert_eq!(reached, 5);
+    assert_eq!(pool.to_string(), "pool: 1 tasks, 0 completed, 0 overdue");
+}
+
+#[test]
+fn stream_yields_results_including_tasks_spawned_mid_stream() {
+    use futures::StreamExt;
+
+    let mut pool: LocalPool<u32> = LocalPool::new();
+    let spawner = pool.spawner();
+    pool.spawn(Box::pin(async move {
+        spawner.spawn(Box::pin(async { 2 })).unwrap();
+        1
+    }));
+
+    let mut seen = Vec::new();
+    minimal_executor::block_on(pool.stream().for_each(|r| {
+        seen.push(r);
+        async {}
+    }));
+    seen.sort();
+    assert_eq!(seen, vec![1, 2]);
+}
+
+#[test]
+fn awaiting_a_pool_inside_another_pools_run_until() {
+    use minimal_executor::NewLocalPool;
+
+    let (tx, rx) = futures::channel::oneshot::channel();
+    let mut inner = NewLocalPool::new();
+    inner.spawn(Box::pin(async move { rx.await.unwrap() * 2 }));
+    inner.spawn(Box::pin(async { 1 }));
+
+    let mut outer: LocalPool<()> = LocalPool::new();
+    outer.spawn(Box::pin(async move { tx.send(5).unwrap() }));
+    let mut results = outer.run_until(&mut inner);
+    results.sort();
+    assert_eq!(results, vec![1, 10]);
+    assert_eq!(futures::executor::block_on(&mut inner), Vec::<i32>::new());
+}
+
+#[test]
+fn poll_all_ready_collects_ready_results_and_reports_leftovers() {
+    use minimal_executor::NewLocalPool;
+
+    let (tx, rx) = futures::channel::oneshot::channel();
+    let mut pool = NewLocalPool::new();
+    pool.spawn(Box::pin(async move { rx.await.unwrap() }));
+    for i in 1..=3 {
+        pool.spawn(Box::pin(async move { i }));
+    }
+    let (mut results, empty) = pool.poll_all_ready();
+    results.sort();
+    assert_eq!(results, vec![1, 2, 3]);
+    assert!(!empty);
+
+    tx.send(4).unwrap();
+    assert_eq!(pool.poll_all_ready(), (vec![4], true));
+    assert_eq!(pool.poll_all_ready(), (vec![], true));
+}
+
+#[test]
+fn new_pool_drain_returns_pool_and_spawner_tasks() {
+    use minimal_executor::NewLocalPool;
+
+    let (tx, rx) = futures::channel::oneshot::channel();
+    let mut pool = NewLocalPool::new().with_task_limit(2);
+    let spawner = pool.spawner();
+    pool.spawn(Box::pin(async { 1 }));
+    pool.spawn(Box::pin(futures::future::pending()));
+    assert_eq!(pool.try_run_one(), Poll::Ready(1));
+    spawner.spawn(Box::pin(async move { rx.await.unwrap() })).unwrap();
+    assert!(spawner.spawn(Box::pin(async { 3 })).is_err());
+
+    let drained = pool.drain();
+    assert_eq!(drained.len(), 2);
+    assert!(pool.run().is_empty());
+    // the drained tasks no longer count against the limit
+    spawner.spawn(Box::pin(async { 3 })).unwrap();
+    assert_eq!(pool.run(), vec![3]);
+
+    let mut other = NewLocalPool::new();
+    for fut in drained {
+        other.spawn(Box::pin(fut));
+    }
+    tx.send(2).unwrap();
+    assert_eq!(other.try_run_one(), Poll::Ready(2));
+}
+
+#[test]
+fn spawn_deadline_reports_tasks_that_miss_their_deadline() {
+    use std::time::{Duration, Instant};
+
+    /// Sleeps past its deadline inside its only poll, then completes.
+    struct SlowPoll;
+
+    impl Future for SlowPoll {
+        type Output = u32;
+
+        fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<u32> {
+            std::thread::sleep(Duration::from_millis(30));
+            Poll::Ready(2)
+        }
+    }
+
+    let mut pool = LocalPool::new();
+    let soon = Instant::now() + Duration::from_millis(10);
+    let hung = pool.spawn_deadline(Box::pin(futures::future::pending()), soon);
+    pool.spawn_deadline(Box::pin(SlowPoll), soon);
+    pool.spawn(Box::pin(async { 1 }));
+    let (mut results, timed_out) = pool.run_with_deadlines();
+    results.sort();
+    assert_eq!(results, vec![1, 2]);
+    assert_eq!(timed_out, vec![hung]);
+    assert!(pool.take_timed_out().is_empty());
+    assert_eq!(pool.to_string(), "pool: 0 tasks, 2 completed, 1 overdue");
+}
+
+#[test]
+fn pending_spawns_counts_tasks_until_the_pool_takes_them_in() {
+    use minimal_executor::NewLocalPool;
+
+    let mut old: LocalPool<u32> = LocalPool::new();
+    old.spawner().spawn(Box::pin(async { 1 })).unwrap();
+    old.spawn(Box::pin(futures::future::pending()));
+    assert_eq!((old.len(), old.pending_spawns()), (2, 1));
+    assert_eq!(old.try_run_one(), Poll::Ready(1));
+    assert_eq!((old.len(), old.pending_spawns()), (1, 0));
+
+    let mut new: NewLocalPool<u32> = NewLocalPool::new();
+    new.spawner().spawn(Box::pin(async { 1 })).unwrap();
+    new.spawn(Box::pin(futures::future::pending()));
+    assert_eq!((new.len(), new.pending_spawns()), (2, 1));
+    assert_eq!(new.try_run_one(), Poll::Ready(1));
+    assert_eq!((new.len(), new.pending_spawns()), (1, 0));
+}
+
+macro_rules! shutdown_rejects_spawns_from_running_tasks {
+    ($name:ident, $pool:ty) => {
+        #[test]
+        fn $name() {
+            let mut pool = <$pool>::new();
+            let spawner = pool.spawner();
+            let inner = spawner.clone();
+            let rejected = Rc::new(Cell::new(false));
+            let seen = rejected.clone();
+            spawner.spawn(Box::pin(async { 1 })).unwrap();
+            pool.spawn(Box::pin(async move {
+                seen.set(inner.spawn(Box::pin(async { 2 })).is_err());
+                3
+            }));
+
+            let mut results = pool.shutdown();
+            results.sort();
+            assert_eq!(results, vec![1, 3]);
+            assert!(rejected.get());
+            assert!(pool.is_shut_down());
+            assert!(pool.is_empty());
+        }
+    };
+}
+
+shutdown_rejects_spawns_from_running_tasks!(old_shutdown_rejects_spawns_from_running_tasks, LocalPool<u32>);
+shutdown_rejects_spawns_from_running_tasks!(new_shutdown_rejects_spawns_from_running_tasks, minimal_executor::NewLocalPool<u32>);
+
+macro_rules! local_spawner_runs_non_send_tasks {
+    ($name:ident, $pool:ty) => {
+        #[test]
+        fn $name() {
+            use futures::task::LocalSpawnExt;
+
+            let mut pool = <$pool>::new();
+            let spawner = pool.local_spawner();
+            let ran = Rc::new(Cell::new(0));
+            let (outer, inner) = (ran.clone(), ran.clone());
+            let nested = spawner.clone();
+            spawner.spawn_local(async move {
+                outer.set(outer.get() + 1);
+                // tasks can keep spawning `!Send` work through the same handle
+                nested.spawn_local(async move { inner.set(inner.get() + 1) }).unwrap();
+            }).unwrap();
+            assert_eq!(pool.len(), 1);
+            assert_eq!(pool.run().len(), 2);
+            assert_eq!(ran.get(), 2);
+
+            drop(pool);
+            assert!(spawner.spawn_local(async {}).is_err());
+        }
+    };
+}
+
+local_spawner_runs_non_send_tasks!(old_local_spawner_runs_non_send_tasks, LocalPool);
+local_spawner_runs_non_send_tasks!(new_local_spawner_runs_non_send_tasks, minimal_executor::NewLocalPool);
+
+macro_rules! as_task_nests_one_pool_in_another {
+    ($name:ident, $pool:ty) => {
+        #[test]
+        fn $name() {
+            let (tx, rx) = futures::channel::oneshot::channel();
+            let mut inner = <$pool>::new();
+            inner.spawn(Box::pin(async { 1 }));
+            // only completes once a task of the outer pool sends, so the inner pool
+            // has to be woken through the outer one
+            inner.spawn(Box::pin(async move { rx.await.unwrap() }));
+
+            let mut outer = <$pool>::new();
+            outer.spawn(Box::pin(inner.as_task().map(|results| results.iter().sum::<usize>())));
+            outer.spawn(Box::pin(async move {
+                tx.send(2).unwrap();
+                0
+            }));
+            let mut results = outer.run();
+            results.sort();
+            assert_eq!(results, vec![0, 3]);
+            drop(outer);
+            assert!(inner.is_empty());
+        }
+    };
+}
+
+as_task_nests_one_pool_in_another!(old_as_task_nests_one_pool_in_another, LocalPool<usize>);
+as_task_nests_one_pool_in_another!(new_as_task_nests_one_pool_in_another, minimal_executor::NewLocalPool<usize>);
+
+#[test]
+fn try_spawn_hands_back_rejected_futures() {
+    use minimal_executor::SpawnErrorKind;
+
+    let mut pool = LocalPool::<u32>::new().with_task_limit(1);
+    let spawner = pool.spawner();
+    spawner.try_spawn(Box::pin(async { 1 })).unwrap();
+    let full = spawner.try_spawn(Box::pin(async { 2 })).unwrap_err();
+    assert_eq!(full.kind, SpawnErrorKind::Full);
+    assert_eq!(pool.run(), vec![1]);
+
+    pool.shutdown();
+    let closed = spawner.try_spawn(full.into_future()).unwrap_err();
+    assert_eq!(closed.kind, SpawnErrorKind::Shutdown);
+    drop(pool);
+    let gone = spawner.try_spawn(closed.future).unwrap_err();
+    assert_eq!(gone.kind, SpawnErrorKind::Shutdown);
+    assert_eq!(futures::executor::block_on(gone.future), 2);
+}
+
+#[test]
+fn bounded_new_pool_rejects_spawns_once_the_backlog_is_full() {
+    use futures::task::SpawnExt;
+    use minimal_executor::{NewLocalPool, SpawnErrorKind};
+
+    let mut pool = NewLocalPool::bounded(2);
+    let spawner = pool.spawner();
+    SpawnExt::spawn(&spawner, async {}).unwrap();
+    SpawnExt::spawn(&spawner, async {}).unwrap();
+    assert!(SpawnExt::spawn(&spawner, async {}).is_err());
+    let err = spawner.try_spawn(Box::pin(async {})).unwrap_err();
+    assert_eq!(err.kind, SpawnErrorKind::Full);
+    // tasks spawned on the pool itself do not count
+    pool.spawn(Box::pin(async {}));
+    assert_eq!(pool.pending_spawns(), 2);
+
+    // once the pool takes the queued tasks in, spawners may queue again
+    assert_eq!(pool.run().len(), 3);
+    spawner.try_spawn(err.future).unwrap();
+    assert_eq!(pool.run().len(), 1);
+}
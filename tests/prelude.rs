@@ -0,0 +1,41 @@
+use minimal_executor::prelude::*;
+
+/// Runs any pool through `PoolExt` alone.
+fn run_all<P: PoolExt<Output = u32>>(pool: &mut P) -> Vec<u32> {
+    let mut results = pool.run_n(usize::MAX);
+    results.sort();
+    results
+}
+
+#[test]
+fn pool_ext_drives_every_pool() {
+    let mut old = LocalPool::new();
+    let mut new = NewLocalPool::new();
+    let mut busy = BusyLocalPool::new(4);
+    for i in 0..3u32 {
+        old.spawn(Box::pin(async move { i }));
+        new.spawn(Box::pin(async move { i }));
+        busy.spawn(Box::pin(async move { i })).unwrap();
+    }
+    assert_eq!(run_all(&mut old), vec![0, 1, 2]);
+    assert_eq!(run_all(&mut new), vec![0, 1, 2]);
+    assert_eq!(run_all(&mut busy), vec![0, 1, 2]);
+}
+
+#[test]
+fn run_until_works_on_the_busy_pool() {
+    let (tx, rx) = futures::channel::oneshot::channel();
+    let mut pool = BusyLocalPool::new(4);
+    pool.spawn(Box::pin(async move { tx.send(7).unwrap() })).unwrap();
+    assert_eq!(pool.run_until(rx), Ok(7));
+    assert!(pool.is_empty());
+}
+
+#[test]
+fn spawn_traits_come_with_the_prelude() {
+    let mut pool = NewLocalPool::new();
+    pool.local_spawner().spawn_local(async {}).unwrap();
+    SpawnExt::spawn(&pool.spawner(), async {}).unwrap();
+    assert_eq!(pool.run().len(), 2);
+    assert_eq!(block_on(async { 3 }), 3);
+}
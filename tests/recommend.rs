@@ -0,0 +1,13 @@
+use minimal_executor::{recommended_pool, PoolKind};
+
+#[test]
+fn small_bounded_workloads_use_busy_pool() {
+    assert_eq!(recommended_pool(2, true), PoolKind::Busy);
+    assert_eq!(recommended_pool(200, true), PoolKind::Busy);
+}
+
+#[test]
+fn large_or_unbounded_workloads_use_futures_unordered() {
+    assert_eq!(recommended_pool(10_000, true), PoolKind::New);
+    assert_eq!(recommended_pool(2, false), PoolKind::New);
+}
@@ -0,0 +1,49 @@
+use minimal_executor::scope;
+use std::cell::Cell;
+use std::pin::pin;
+
+#[test]
+fn scope_drives_borrowing_tasks_to_completion_before_returning() {
+    let counter = Cell::new(0);
+    let pinned = pin!(async { counter.set(counter.get() + 10) });
+    let (before, results) = scope(|s| {
+        for i in 0..3 {
+            let counter = &counter;
+            s.spawn_borrowed(async move {
+                futures::future::ready(()).await;
+                counter.set(counter.get() + i);
+            });
+        }
+        s.spawn(pinned);
+        // nothing has run yet; the tasks only run once the closure returns
+        counter.get()
+    });
+    assert_eq!((before, results.len()), (0, 4));
+    assert_eq!(counter.get(), 13);
+}
+
+#[test]
+fn scope_drops_unfinished_tasks_when_a_task_panics() {
+    let dropped = Cell::new(false);
+
+    struct SetOnDrop<'a>(&'a Cell<bool>);
+
+    impl Drop for SetOnDrop<'_> {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        scope(|s| {
+            let guard = SetOnDrop(&dropped);
+            s.spawn_borrowed(async move {
+                let _guard = guard;
+                futures::future::pending::<()>().await;
+            });
+            s.spawn_borrowed(async { panic!("task failed") });
+        })
+    }));
+    assert!(panicked.is_err());
+    assert!(dropped.get());
+}
@@ -0,0 +1,163 @@
+use minimal_executor::{local_waker, LocalWake};
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+#[derive(Default)]
+struct WakeCount(Cell<usize>);
+
+impl LocalWake for WakeCount {
+    fn wake_by_ref(self: &Rc<Self>) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+/// A `!Send` future that wakes itself through a cloned waker a few times.
+struct SelfWaking {
+    remaining: Rc<Cell<usize>>,
+}
+
+impl Future for SelfWaking {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.remaining.get() == 0 {
+            return Poll::Ready(());
+        }
+        self.remaining.set(self.remaining.get() - 1);
+        // exercise the by-value path of the vtable
+        let waker = cx.waker().clone();
+        waker.wake();
+        Poll::Pending
+    }
+}
+
+#[test]
+fn local_waker_drives_self_waking_future() {
+    let wakes = Rc::new(WakeCount::default());
+    // SAFETY: the waker never leaves this thread
+    let waker = unsafe { local_waker(wakes.clone()) };
+    let mut cx = Context::from_waker(&waker);
+
+    let mut fut = SelfWaking { remaining: Rc::new(Cell::new(3)) };
+    let mut polls = 0;
+    while Pin::new(&mut fut).poll(&mut cx).is_pending() {
+        polls += 1;
+    }
+
+    assert_eq!(polls, 3);
+    assert_eq!(wakes.0.get(), 3);
+    drop(waker);
+    assert_eq!(Rc::strong_count(&wakes), 1);
+}
+
+/// Clones its waker twice, wakes one clone by value and drops the other, so the whole
+/// vtable is exercised. Run under `cargo miri test` to check it for UB.
+struct CloneWakeDrop {
+    remaining: usize,
+}
+
+impl Future for CloneWakeDrop {
+    type Output = usize;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<usize> {
+        if self.remaining == 0 {
+            return Poll::Ready(7);
+        }
+        self.remaining -= 1;
+        let woken = cx.waker().clone();
+        let dropped = woken.clone();
+        woken.wake();
+        drop(dropped);
+        Poll::Pending
+    }
+}
+
+/// Polls `fut` to completion with `waker`, returning its output.
+fn poll_with(waker: &std::task::Waker, mut fut: impl Future<Output = usize> + Unpin) -> usize {
+    let mut cx = Context::from_waker(waker);
+    loop {
+        if let Poll::Ready(out) = Pin::new(&mut fut).poll(&mut cx) {
+            return out;
+        }
+    }
+}
+
+#[test]
+fn waker_vtables_survive_clone_wake_and_drop() {
+    use minimal_executor::waker::noop_waker;
+    use minimal_executor::{waker_from_arc, SimpleWaker};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct Count(AtomicUsize);
+
+    impl SimpleWaker for Count {
+        fn wake(&self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let owned = Arc::new(Count(AtomicUsize::new(0)));
+    let waker = waker_from_arc(owned.clone());
+    assert_eq!(poll_with(&waker, CloneWakeDrop { remaining: 3 }), 7);
+    assert_eq!(owned.0.load(Ordering::Relaxed), 3);
+    drop(waker);
+    assert_eq!(Arc::strong_count(&owned), 1);
+
+    let local = Rc::new(WakeCount::default());
+    // SAFETY: the waker never leaves this thread
+    let waker = unsafe { local_waker(local.clone()) };
+    assert_eq!(poll_with(&waker, CloneWakeDrop { remaining: 3 }), 7);
+    assert_eq!(local.0.get(), 3);
+    drop(waker);
+    assert_eq!(Rc::strong_count(&local), 1);
+
+    // borrows a static, so clones and drops must leave it alone
+    assert_eq!(poll_with(&noop_waker(), CloneWakeDrop { remaining: 3 }), 7);
+}
+
+#[test]
+fn counting_waker_counts_wakes_across_threads() {
+    use minimal_executor::waker::{noop_waker, CountingWaker};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<CountingWaker>();
+
+    let counter = CountingWaker::new();
+    let waker = counter.waker();
+    let remote = waker.clone();
+    std::thread::spawn(move || remote.wake()).join().unwrap();
+    waker.wake_by_ref();
+    assert_eq!(counter.count(), 2);
+
+    // the no-op waker can be woken and dropped freely
+    noop_waker().wake();
+}
+
+#[test]
+fn arc_waker_keeps_its_target_alive_across_threads() {
+    use minimal_executor::{waker_from_arc, SimpleWaker};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct Interrupt(AtomicUsize);
+
+    impl SimpleWaker for Interrupt {
+        fn wake(&self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let irq = Arc::new(Interrupt(AtomicUsize::new(0)));
+    let waker = waker_from_arc(irq.clone());
+    let clones: Vec<_> = (0..4).map(|_| waker.clone()).collect();
+    assert_eq!(Arc::strong_count(&irq), 6);
+    std::thread::spawn(move || clones.into_iter().for_each(|w| w.wake())).join().unwrap();
+    waker.wake_by_ref();
+    assert_eq!(irq.0.load(Ordering::Relaxed), 5);
+    drop(waker);
+    assert_eq!(Arc::strong_count(&irq), 1);
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "wasm")]
+use minimal_executor::{spawn_on_microtask, BusyLocalPool, NewLocalPool};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+type Callbacks = VecDeque<Box<dyn FnOnce()>>;
+
+/// Stands in for the browser's microtask queue.
+#[derive(Clone, Default)]
+struct Microtasks(Rc<RefCell<Callbacks>>);
+
+impl Microtasks {
+    fn schedule(&self) -> impl Fn(Box<dyn FnOnce()>) + 'static {
+        let queue = self.0.clone();
+        move |task| queue.borrow_mut().push_back(task)
+    }
+
+    fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    /// Runs queued callbacks until none are left, returning how many ran.
+    fn run(&self) -> usize {
+        let mut ran = 0;
+        loop {
+            let task = self.0.borrow_mut().pop_front();
+            match task {
+                Some(task) => task(),
+                None => return ran,
+            }
+            ran += 1;
+        }
+    }
+}
+
+#[test]
+fn microtasks_are_scheduled_only_on_wake_ups() {
+    let microtasks = Microtasks::default();
+    let done = Rc::new(Cell::new(false));
+    let (tx, rx) = futures::channel::oneshot::channel::<()>();
+    let mut pool = NewLocalPool::new();
+    let flag = done.clone();
+    pool.spawn(Box::pin(async move {
+        rx.await.unwrap();
+        flag.set(true);
+    }));
+    unsafe { spawn_on_microtask(pool, microtasks.schedule()) };
+
+    microtasks.run();
+    assert!(!done.get());
+    // nothing happens until the task is woken
+    assert_eq!(microtasks.len(), 0);
+
+    tx.send(()).unwrap();
+    assert_eq!(microtasks.len(), 1);
+    assert_eq!(microtasks.run(), 1);
+    assert!(done.get());
+}
+
+#[test]
+fn wake_ups_before_the_callback_runs_are_merged() {
+    let microtasks = Microtasks::default();
+    let mut pool = BusyLocalPool::new(4);
+    let spawner = pool.spawner();
+    pool.spawn(Box::pin(futures::future::pending::<()>())).unwrap();
+    unsafe { spawn_on_microtask(pool, microtasks.schedule()) };
+    assert_eq!(microtasks.len(), 1);
+
+    // tasks spawned later are picked up by the next callback
+    let ran = Arc::new(AtomicUsize::new(0));
+    for _ in 0..3 {
+        let ran = ran.clone();
+        spawner.spawn(Box::pin(async move { ran.fetch_add(1, Ordering::Relaxed); })).unwrap();
+    }
+    microtasks.run();
+    assert_eq!(ran.load(Ordering::Relaxed), 3);
+}